@@ -6,6 +6,16 @@
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+extern "C" {
+    // blacklisted in build.rs, so it needs to be declared by hand
+    pub fn fingerprint_generator_create(
+        generator: *mut *mut fingerprint_generator,
+        iterations: ::std::os::raw::c_int,
+        scannable_version: ::std::os::raw::c_int,
+        global_context: *mut signal_context,
+    ) -> ::std::os::raw::c_int;
+}
+
 pub mod protobuf {
     include!(concat!(env!("OUT_DIR"), "/protobuf.rs"));
 }
@@ -24,18 +24,20 @@
 //! cit: https://github.com/signalapp/libsignal-protocol-c#client-install-time
 
 use failure::Error;
-use libsignal_protocol::Context;
-use std::time::SystemTime;
+use libsignal_protocol::{Context, PreKeyId, SignedPreKeyId, Timestamp};
 
 fn main() -> Result<(), Error> {
     let ctx = Context::default();
     let extended_range = 0;
-    let start = 123;
+    let start = PreKeyId::new(123);
     let pre_key_count = 20;
 
     let identity_key_pair = ctx.generate_identity_key_pair()?;
-    let signed_pre_key =
-        ctx.generate_signed_pre_key(&identity_key_pair, 5, SystemTime::now())?;
+    let signed_pre_key = ctx.generate_signed_pre_key(
+        &identity_key_pair,
+        SignedPreKeyId::new(5),
+        Timestamp::now(),
+    )?;
     println!(
         "Signed pre key ID: {} at {:?}",
         signed_pre_key.id(),
@@ -45,8 +45,9 @@
 
 use failure::Error;
 use libsignal_protocol::{
-    Address, Buffer, Context, IdentityKeyStore, InternalError, PreKeyBundle,
-    PreKeyStore, SessionBuilder, SessionStore, SignedPreKeyStore,
+    Address, Buffer, Context, DeviceId, IdentityKeyPair, IdentityKeyStore,
+    InternalError, PreKeyBundle, PreKeyId, PreKeyStore, SessionBuilder,
+    SessionStore, SignedPreKeyId, SignedPreKeyStore,
 };
 use std::io::{self, Write};
 
@@ -65,15 +66,15 @@ fn main() -> Result<(), Error> {
         identity_key_store,
     )?;
 
-    let addr = Address::new("+14159998888", 1);
+    let addr = Address::new("+14159998888", DeviceId::new(1)?);
 
     // Instantiate a session_builder for a recipient address.
-    let session_builder = SessionBuilder::new(&ctx, store_ctx, addr);
+    let session_builder = SessionBuilder::new(&ctx, store_ctx, addr)?;
 
     // Build a session with a pre key retrieved from the server.
     let pre_key_bundle = PreKeyBundle::builder().build()?;
 
-    session_builder.process_pre_key_bundle(&pre_key_bundle);
+    session_builder.process_pre_key_bundle(&pre_key_bundle)?;
 
     Ok(())
 }
@@ -82,34 +83,46 @@ fn main() -> Result<(), Error> {
 struct BasicPreKeyStore {}
 
 impl PreKeyStore for BasicPreKeyStore {
-    fn load(&self, _id: u32, _writer: &mut dyn Write) -> io::Result<()> {
+    fn load(&self, _id: PreKeyId, _writer: &mut dyn Write) -> io::Result<()> {
         unimplemented!()
     }
 
-    fn store(&self, _id: u32, _body: &[u8]) -> Result<(), InternalError> {
+    fn store(&self, _id: PreKeyId, _body: &[u8]) -> Result<(), InternalError> {
         unimplemented!()
     }
 
-    fn contains(&self, _id: u32) -> bool { unimplemented!() }
+    fn contains(&self, _id: PreKeyId) -> bool { unimplemented!() }
 
-    fn remove(&self, _id: u32) -> Result<(), InternalError> { unimplemented!() }
+    fn remove(&self, _id: PreKeyId) -> Result<(), InternalError> {
+        unimplemented!()
+    }
 }
 
 #[derive(Debug, Default)]
 struct BasicSignedPreKeyStore {}
 
 impl SignedPreKeyStore for BasicSignedPreKeyStore {
-    fn load(&self, _id: u32, _writer: &mut dyn Write) -> io::Result<()> {
+    fn load(
+        &self,
+        _id: SignedPreKeyId,
+        _writer: &mut dyn Write,
+    ) -> io::Result<()> {
         unimplemented!()
     }
 
-    fn store(&self, _id: u32, _body: &[u8]) -> Result<(), InternalError> {
+    fn store(
+        &self,
+        _id: SignedPreKeyId,
+        _body: &[u8],
+    ) -> Result<(), InternalError> {
         unimplemented!()
     }
 
-    fn contains(&self, _id: u32) -> bool { unimplemented!() }
+    fn contains(&self, _id: SignedPreKeyId) -> bool { unimplemented!() }
 
-    fn remove(&self, _id: u32) -> Result<(), InternalError> { unimplemented!() }
+    fn remove(&self, _id: SignedPreKeyId) -> Result<(), InternalError> {
+        unimplemented!()
+    }
 }
 
 #[derive(Debug, Default)]
@@ -123,10 +136,66 @@ impl SessionStore for BasicSessionStore {
         unimplemented!()
     }
 
-    fn get_sub_devuce_sessions(&self) { unimplemented!() }
+    fn get_sub_device_sessions(
+        &self,
+        _name: &str,
+    ) -> Result<Vec<DeviceId>, InternalError> {
+        unimplemented!()
+    }
+
+    fn store_session(
+        &self,
+        _address: &Address,
+        _record: &[u8],
+        _user_record: &[u8],
+    ) -> Result<(), InternalError> {
+        unimplemented!()
+    }
+
+    fn contains_session(
+        &self,
+        _address: &Address,
+    ) -> Result<bool, InternalError> {
+        unimplemented!()
+    }
+
+    fn delete_session(
+        &self,
+        _address: &Address,
+    ) -> Result<bool, InternalError> {
+        unimplemented!()
+    }
+
+    fn delete_all_sessions(&self, _name: &str) -> Result<usize, InternalError> {
+        unimplemented!()
+    }
 }
 
 #[derive(Debug, Default)]
 struct BasicIdentityKeyStore {}
 
-impl IdentityKeyStore for BasicIdentityKeyStore {}
+impl IdentityKeyStore for BasicIdentityKeyStore {
+    fn get_identity_key_pair(&self) -> Result<IdentityKeyPair, InternalError> {
+        unimplemented!()
+    }
+
+    fn get_local_registration_id(&self) -> Result<u32, InternalError> {
+        unimplemented!()
+    }
+
+    fn save_identity(
+        &self,
+        _address: &Address,
+        _identity_key: &[u8],
+    ) -> Result<(), InternalError> {
+        unimplemented!()
+    }
+
+    fn is_trusted_identity(
+        &self,
+        _address: &Address,
+        _identity_key: &[u8],
+    ) -> Result<bool, InternalError> {
+        unimplemented!()
+    }
+}
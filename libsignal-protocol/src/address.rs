@@ -1,5 +1,10 @@
+use crate::ids::DeviceId;
 use libsignal_protocol_sys as sys;
-use std::{marker::PhantomData, os::raw::c_char};
+use std::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    os::raw::c_char,
+};
 
 pub struct Address<'a> {
     raw: sys::signal_protocol_address,
@@ -7,11 +12,11 @@ pub struct Address<'a> {
 }
 
 impl<'a> Address<'a> {
-    pub fn new(name: &'a str, device_id: i32) -> Address<'a> {
+    pub fn new(name: &'a str, device_id: DeviceId) -> Address<'a> {
         let raw = sys::signal_protocol_address {
             name: name.as_ptr() as *const c_char,
             name_len: name.len(),
-            device_id,
+            device_id: device_id.raw(),
         };
 
         Address {
@@ -20,6 +25,22 @@ impl<'a> Address<'a> {
         }
     }
 
+    /// Copy an address passed to us by `libsignal-protocol-c`.
+    ///
+    /// # Safety
+    ///
+    /// The name must stay valid for the lifetime `'a`.
+    pub(crate) unsafe fn from_raw(
+        raw: *const sys::signal_protocol_address,
+    ) -> Address<'a> {
+        assert!(!raw.is_null());
+
+        Address {
+            raw: *raw,
+            _string_lifetime: PhantomData,
+        }
+    }
+
     pub(crate) fn raw(&self) -> *const sys::signal_protocol_address {
         &self.raw
     }
@@ -37,5 +58,56 @@ impl<'a> Address<'a> {
         std::str::from_utf8(self.bytes())
     }
 
-    pub fn device_id(&self) -> i32 { self.raw.device_id }
+    pub fn device_id(&self) -> DeviceId {
+        DeviceId::from_raw(self.raw.device_id)
+    }
+}
+
+/// An [`Address`] which owns its name.
+///
+/// Some `libsignal-protocol-c` objects (e.g. `session_cipher`) hold onto the
+/// address pointer they were created with instead of copying it, so it needs
+/// to live at a stable location for as long as they do.
+pub(crate) struct OwnedAddress {
+    _name: Box<[u8]>,
+    raw: Box<sys::signal_protocol_address>,
+}
+
+impl OwnedAddress {
+    pub(crate) fn new(address: &Address<'_>) -> OwnedAddress {
+        let name: Box<[u8]> = address.bytes().into();
+        let raw = Box::new(sys::signal_protocol_address {
+            name: name.as_ptr() as *const c_char,
+            name_len: name.len(),
+            device_id: address.raw.device_id,
+        });
+
+        OwnedAddress { _name: name, raw }
+    }
+
+    pub(crate) fn raw(&self) -> *const sys::signal_protocol_address {
+        &*self.raw
+    }
+
+    pub(crate) fn as_address(&self) -> Address<'_> {
+        Address {
+            raw: *self.raw,
+            _string_lifetime: PhantomData,
+        }
+    }
+}
+
+impl<'a> Debug for Address<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Address")
+            .field("name", &String::from_utf8_lossy(self.bytes()))
+            .field("device_id", &self.device_id())
+            .finish()
+    }
+}
+
+impl Debug for OwnedAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&self.as_address(), f)
+    }
 }
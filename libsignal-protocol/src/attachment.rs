@@ -0,0 +1,412 @@
+use crate::{
+    crypto::{Sha256Digest, Sha256Hmac, SignalCipherType},
+    errors::InternalError,
+    redact::Redacted,
+    Context,
+};
+use failure::Error;
+use std::{
+    fmt::{self, Debug, Formatter},
+    io::{self, Read, Write},
+};
+
+const CIPHER_KEY_LEN: usize = 32;
+const MAC_KEY_LEN: usize = 32;
+const IV_LEN: usize = 16;
+const MAC_LEN: usize = 32;
+/// How much plaintext is encrypted at a time when streaming. This must be a
+/// multiple of the AES block size.
+const STREAM_CHUNK_LEN: usize = 64 * 1024;
+
+/// The keys used to encrypt a single attachment.
+///
+/// Attachments are encrypted using the standard Signal attachment scheme,
+/// AES-256-CBC with PKCS#5 padding followed by a HMAC-SHA256 over the IV and
+/// ciphertext. The encrypted blob is laid out as `iv || ciphertext || mac`,
+/// and its SHA-256 [digest](AttachmentKeys::digest) is sent alongside the
+/// keys so the recipient can check they downloaded the right thing.
+///
+/// All cryptography is done using the [`Crypto`] provider the [`Context`] was
+/// created with.
+///
+/// [`Crypto`]: crate::crypto::Crypto
+#[derive(Clone, PartialEq, Eq)]
+pub struct AttachmentKeys {
+    cipher_key: [u8; CIPHER_KEY_LEN],
+    mac_key: [u8; MAC_KEY_LEN],
+}
+
+impl AttachmentKeys {
+    /// The length of the serialized keys (a 32 byte AES key followed by a 32
+    /// byte HMAC key).
+    pub const LEN: usize = CIPHER_KEY_LEN + MAC_KEY_LEN;
+
+    /// Generate a fresh set of random attachment keys.
+    pub fn generate(ctx: &Context) -> Result<AttachmentKeys, Error> {
+        let mut bytes = [0; Self::LEN];
+        ctx.crypto().fill_random(&mut bytes)?;
+
+        AttachmentKeys::from_bytes(&bytes)
+    }
+
+    /// Load attachment keys previously serialized with
+    /// [`AttachmentKeys::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<AttachmentKeys, Error> {
+        if bytes.len() != Self::LEN {
+            return Err(InternalError::InvalidKey.into());
+        }
+
+        let mut cipher_key = [0; CIPHER_KEY_LEN];
+        let mut mac_key = [0; MAC_KEY_LEN];
+        cipher_key.copy_from_slice(&bytes[..CIPHER_KEY_LEN]);
+        mac_key.copy_from_slice(&bytes[CIPHER_KEY_LEN..]);
+
+        Ok(AttachmentKeys {
+            cipher_key,
+            mac_key,
+        })
+    }
+
+    /// Serialize the keys so they can be sent to the recipient alongside the
+    /// attachment pointer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::LEN);
+        bytes.extend_from_slice(&self.cipher_key);
+        bytes.extend_from_slice(&self.mac_key);
+        bytes
+    }
+
+    /// Encrypt an attachment, returning `iv || ciphertext || mac`.
+    pub fn encrypt(
+        &self,
+        ctx: &Context,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let crypto = ctx.crypto();
+
+        let mut iv = [0; IV_LEN];
+        crypto.fill_random(&mut iv)?;
+
+        let ciphertext = crypto.encrypt(
+            SignalCipherType::AesCbcPkcs5,
+            &self.cipher_key,
+            &iv,
+            plaintext,
+        )?;
+
+        let mut encrypted =
+            Vec::with_capacity(IV_LEN + ciphertext.len() + MAC_LEN);
+        encrypted.extend_from_slice(&iv);
+        encrypted.extend_from_slice(&ciphertext);
+
+        let mac = self.mac(ctx, &encrypted)?;
+        encrypted.extend_from_slice(&mac);
+
+        Ok(encrypted)
+    }
+
+    /// Encrypt an attachment as it is read from `plaintext`, writing
+    /// `iv || ciphertext || mac` to `encrypted` and returning its digest.
+    pub fn encrypt_stream<R: Read, W: Write>(
+        &self,
+        ctx: &Context,
+        mut plaintext: R,
+        encrypted: W,
+    ) -> Result<Vec<u8>, Error> {
+        let crypto = ctx.crypto();
+        let mut output = DigestingWriter {
+            writer: encrypted,
+            hmac: crypto.hmac_sha256(&self.mac_key)?,
+            digest: crypto.sha256_digest()?,
+        };
+
+        let mut iv = [0; IV_LEN];
+        crypto.fill_random(&mut iv)?;
+        output.write_all(&iv)?;
+
+        // CBC mode can be split into block-aligned chunks by using the last
+        // ciphertext block of one chunk as the IV for the next. Only the
+        // final chunk keeps its padding.
+        let mut chunk = vec![0; STREAM_CHUNK_LEN];
+        let mut iv = iv.to_vec();
+
+        loop {
+            let len = read_chunk(&mut plaintext, &mut chunk)?;
+            let ciphertext = crypto.encrypt(
+                SignalCipherType::AesCbcPkcs5,
+                &self.cipher_key,
+                &iv,
+                &chunk[..len],
+            )?;
+
+            if len < STREAM_CHUNK_LEN {
+                output.write_all(&ciphertext)?;
+                break;
+            }
+
+            let ciphertext = &ciphertext[..STREAM_CHUNK_LEN];
+            output.write_all(ciphertext)?;
+            iv = ciphertext[STREAM_CHUNK_LEN - IV_LEN..].to_vec();
+        }
+
+        let mac = output.hmac.finalize()?;
+        if mac.len() < MAC_LEN {
+            return Err(InternalError::InvalidMAC.into());
+        }
+        output.digest.update(&mac[..MAC_LEN])?;
+        output.writer.write_all(&mac[..MAC_LEN])?;
+
+        output.digest.finalize().map_err(Error::from)
+    }
+
+    /// Verify the MAC on an encrypted attachment and decrypt it.
+    ///
+    /// The MAC is always checked before any decryption is attempted, failing
+    /// with [`InternalError::InvalidMAC`] if the attachment was tampered with.
+    pub fn decrypt(
+        &self,
+        ctx: &Context,
+        encrypted: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        if encrypted.len() < IV_LEN + MAC_LEN {
+            return Err(InternalError::InvalidMessage.into());
+        }
+
+        let (body, their_mac) = encrypted.split_at(encrypted.len() - MAC_LEN);
+        let our_mac = self.mac(ctx, body)?;
+
+        if !constant_time_eq(&our_mac, their_mac) {
+            return Err(InternalError::InvalidMAC.into());
+        }
+
+        let (iv, ciphertext) = body.split_at(IV_LEN);
+        let plaintext = ctx.crypto().decrypt(
+            SignalCipherType::AesCbcPkcs5,
+            &self.cipher_key,
+            iv,
+            ciphertext,
+        )?;
+
+        Ok(plaintext)
+    }
+
+    /// Decrypt an attachment read from `encrypted`, writing the plaintext to
+    /// `plaintext`.
+    ///
+    /// If a `digest` is provided the attachment must match it, failing with
+    /// [`InternalError::InvalidMessage`] otherwise. Nothing is written until
+    /// the digest and MAC have been checked, so the encrypted attachment is
+    /// buffered in memory.
+    pub fn decrypt_stream<R: Read, W: Write>(
+        &self,
+        ctx: &Context,
+        mut encrypted: R,
+        mut plaintext: W,
+        digest: Option<&[u8]>,
+    ) -> Result<(), Error> {
+        let mut buffer = Vec::new();
+        encrypted.read_to_end(&mut buffer)?;
+
+        if let Some(digest) = digest {
+            let actual = AttachmentKeys::digest(ctx, &buffer)?;
+            if !constant_time_eq(&actual, digest) {
+                return Err(InternalError::InvalidMessage.into());
+            }
+        }
+
+        let decrypted = self.decrypt(ctx, &buffer)?;
+        plaintext.write_all(&decrypted)?;
+
+        Ok(())
+    }
+
+    /// The SHA-256 digest of an encrypted attachment (`iv || ciphertext ||
+    /// mac`).
+    pub fn digest(ctx: &Context, encrypted: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut digest = ctx.crypto().sha256_digest()?;
+        digest.update(encrypted)?;
+
+        digest.finalize().map_err(Error::from)
+    }
+
+    fn mac(&self, ctx: &Context, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut hmac = ctx.crypto().hmac_sha256(&self.mac_key)?;
+        hmac.update(data)?;
+        let mac = hmac.finalize()?;
+
+        if mac.len() < MAC_LEN {
+            return Err(InternalError::InvalidMAC.into());
+        }
+
+        Ok(mac[..MAC_LEN].to_vec())
+    }
+}
+
+impl Debug for AttachmentKeys {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AttachmentKeys")
+            .field("cipher_key", &Redacted)
+            .field("mac_key", &Redacted)
+            .finish()
+    }
+}
+
+/// A [`Write`]r which feeds everything written through it into the HMAC and
+/// digest of an encrypted attachment.
+struct DigestingWriter<W> {
+    writer: W,
+    hmac: Box<dyn Sha256Hmac>,
+    digest: Box<dyn Sha256Digest>,
+}
+
+impl<W: Write> Write for DigestingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        let buf = &buf[..written];
+
+        self.hmac.update(buf).map_err(to_io_error)?;
+        self.digest.update(buf).map_err(to_io_error)?;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> { self.writer.flush() }
+}
+
+// io::Error::other() is newer than our minimum supported Rust version
+#[allow(clippy::io_other_error)]
+fn to_io_error(err: InternalError) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err.to_string())
+}
+
+/// Read until `buffer` is full or the reader runs out, returning how many
+/// bytes were read.
+fn read_chunk<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+
+    while len < buffer.len() {
+        match reader.read(&mut buffer[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(len)
+}
+
+/// Compare two byte strings without short-circuiting on the first mismatch.
+pub(crate) fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    if left.len() != right.len() {
+        return false;
+    }
+
+    left.iter().zip(right).fold(0, |acc, (l, r)| acc | (l ^ r)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "crypto-openssl")]
+    use crate::crypto::OpenSSLCrypto;
+
+    #[test]
+    fn keys_round_trip_through_bytes() {
+        let bytes: Vec<u8> = (0..AttachmentKeys::LEN as u8).collect();
+
+        let keys = AttachmentKeys::from_bytes(&bytes).unwrap();
+
+        assert_eq!(keys.to_bytes(), bytes);
+        assert!(AttachmentKeys::from_bytes(&bytes[1..]).is_err());
+    }
+
+    #[test]
+    fn truncated_attachments_are_rejected() {
+        let ctx = Context::default();
+        let keys = AttachmentKeys::from_bytes(&[0; 64]).unwrap();
+
+        let got = keys.decrypt(&ctx, &[0; IV_LEN + MAC_LEN - 1]);
+
+        assert!(got.is_err());
+    }
+
+    #[test]
+    fn comparison_checks_every_byte() {
+        assert!(constant_time_eq(b"signal", b"signal"));
+        assert!(!constant_time_eq(b"signal", b"signaL"));
+        assert!(!constant_time_eq(b"signal", b"signals"));
+    }
+
+    #[test]
+    #[cfg(feature = "crypto-openssl")]
+    fn encrypt_and_decrypt_an_attachment() {
+        let ctx = Context::new(OpenSSLCrypto).unwrap();
+        let keys = AttachmentKeys::generate(&ctx).unwrap();
+        let plaintext = b"Hello, World!";
+
+        let mut encrypted = keys.encrypt(&ctx, plaintext).unwrap();
+        let got = keys.decrypt(&ctx, &encrypted).unwrap();
+        assert_eq!(got, plaintext);
+
+        encrypted[IV_LEN] ^= 0x01;
+        assert!(keys.decrypt(&ctx, &encrypted).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "crypto-openssl")]
+    fn streamed_attachments_round_trip() {
+        let ctx = Context::new(OpenSSLCrypto).unwrap();
+        let keys = AttachmentKeys::generate(&ctx).unwrap();
+        let lengths = vec![
+            0,
+            1,
+            STREAM_CHUNK_LEN - 1,
+            STREAM_CHUNK_LEN,
+            2 * STREAM_CHUNK_LEN + 5,
+        ];
+
+        for len in lengths {
+            let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let mut encrypted = Vec::new();
+
+            let digest = keys
+                .encrypt_stream(&ctx, &plaintext[..], &mut encrypted)
+                .unwrap();
+
+            let padded_len = (len / IV_LEN + 1) * IV_LEN;
+            assert_eq!(encrypted.len(), IV_LEN + padded_len + MAC_LEN);
+            assert_eq!(
+                digest,
+                AttachmentKeys::digest(&ctx, &encrypted).unwrap()
+            );
+            assert_eq!(keys.decrypt(&ctx, &encrypted).unwrap(), plaintext);
+
+            let mut got = Vec::new();
+            keys.decrypt_stream(&ctx, &encrypted[..], &mut got, Some(&digest))
+                .unwrap();
+            assert_eq!(got, plaintext, "length {}", len);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "crypto-openssl")]
+    fn attachments_must_match_their_digest() {
+        let ctx = Context::new(OpenSSLCrypto).unwrap();
+        let keys = AttachmentKeys::generate(&ctx).unwrap();
+        let encrypted = keys.encrypt(&ctx, b"Hello, World!").unwrap();
+        let other = keys.encrypt(&ctx, b"Hello, World!").unwrap();
+        let digest = AttachmentKeys::digest(&ctx, &other).unwrap();
+        let mut got = Vec::new();
+
+        let err = keys
+            .decrypt_stream(&ctx, &encrypted[..], &mut got, Some(&digest))
+            .unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<InternalError>(),
+            Some(&InternalError::InvalidMessage)
+        );
+        assert!(got.is_empty());
+    }
+}
@@ -0,0 +1,114 @@
+use crate::{attachment::AttachmentKeys, errors::InternalError, Context};
+use failure::Error;
+
+const HKDF_VERSION: i32 = 3;
+const KEY_LEN: usize = 32;
+
+/// The HKDF `info` used to derive the backup encryption key.
+pub const BACKUP_CIPHER_KEY_LABEL: &[u8] =
+    b"libsignal-protocol-rs backup cipher key v1";
+/// The HKDF `info` used to derive the backup MAC key.
+pub const BACKUP_MAC_KEY_LABEL: &[u8] =
+    b"libsignal-protocol-rs backup mac key v1";
+
+/// The keys used to encrypt and authenticate a backup, derived from a
+/// master secret held by the user.
+///
+/// Both keys are derived with HKDF (no salt) using
+/// [`BACKUP_CIPHER_KEY_LABEL`] and [`BACKUP_MAC_KEY_LABEL`] as the `info`.
+/// Backups are encrypted with the same AES-256-CBC + HMAC-SHA256 scheme as
+/// [`AttachmentKeys`], laid out as `iv || ciphertext || mac`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupKeys {
+    keys: AttachmentKeys,
+}
+
+impl BackupKeys {
+    /// The length of the master secret.
+    pub const MASTER_SECRET_LEN: usize = 32;
+
+    /// Generate a fresh random master secret.
+    pub fn generate_master_secret(
+        ctx: &Context,
+    ) -> Result<[u8; Self::MASTER_SECRET_LEN], Error> {
+        let mut secret = [0; Self::MASTER_SECRET_LEN];
+        ctx.random_bytes(&mut secret)?;
+
+        Ok(secret)
+    }
+
+    /// Derive the backup keys from a master secret.
+    pub fn derive(
+        ctx: &Context,
+        master_secret: &[u8],
+    ) -> Result<BackupKeys, Error> {
+        if master_secret.len() != Self::MASTER_SECRET_LEN {
+            return Err(InternalError::InvalidKey.into());
+        }
+
+        let hkdf = ctx.create_hkdf(HKDF_VERSION)?;
+        let mut bytes = hkdf.derive_secrets(
+            KEY_LEN,
+            master_secret,
+            &[],
+            BACKUP_CIPHER_KEY_LABEL,
+        )?;
+        bytes.extend(hkdf.derive_secrets(
+            KEY_LEN,
+            master_secret,
+            &[],
+            BACKUP_MAC_KEY_LABEL,
+        )?);
+
+        Ok(BackupKeys {
+            keys: AttachmentKeys::from_bytes(&bytes)?,
+        })
+    }
+
+    /// Encrypt a backup, returning `iv || ciphertext || mac`.
+    pub fn encrypt(
+        &self,
+        ctx: &Context,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        self.keys.encrypt(ctx, plaintext)
+    }
+
+    /// Verify the MAC on an encrypted backup and decrypt it, failing with
+    /// [`InternalError::InvalidMAC`] if it was tampered with.
+    pub fn decrypt(
+        &self,
+        ctx: &Context,
+        encrypted: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        self.keys.decrypt(ctx, encrypted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "crypto-openssl")]
+    use crate::crypto::OpenSSLCrypto;
+
+    #[test]
+    fn master_secrets_must_be_the_right_length() {
+        let ctx = Context::default();
+
+        assert!(BackupKeys::derive(&ctx, &[0; 16]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "crypto-openssl")]
+    fn derivation_is_deterministic() {
+        let ctx = Context::new(OpenSSLCrypto).unwrap();
+        let secret = BackupKeys::generate_master_secret(&ctx).unwrap();
+
+        let first = BackupKeys::derive(&ctx, &secret).unwrap();
+        let second = BackupKeys::derive(&ctx, &secret).unwrap();
+
+        assert!(first == second);
+        let encrypted = first.encrypt(&ctx, b"backup").unwrap();
+        assert_eq!(second.decrypt(&ctx, &encrypted).unwrap(), b"backup");
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
     cmp::{Ord, Ordering},
+    fmt::{self, Debug, Formatter},
     io::{self, Write},
     mem,
     ops::{Index, IndexMut},
@@ -28,7 +29,7 @@ impl Buffer {
     pub fn len(&self) -> usize { unsafe { sys::signal_buffer_len(self.raw) } }
 
     /// Is the buffer empty?
-    pub fn is_empty(&self) -> bool { self.len() > 0 }
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
 
     /// Extract the underlying raw pointer.
     ///
@@ -164,6 +165,13 @@ impl Clone for Buffer {
     }
 }
 
+/// Buffers often hold key material, so only their length is shown.
+impl Debug for Buffer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Buffer").field("len", &self.len()).finish()
+    }
+}
+
 impl Drop for Buffer {
     fn drop(&mut self) {
         unsafe {
@@ -189,6 +197,12 @@ mod tests {
         assert_eq!(buffer.len(), cap);
     }
 
+    #[test]
+    fn only_zero_length_buffers_are_empty() {
+        assert!(Buffer::new().is_empty());
+        assert!(!Buffer::with_capacity(1).is_empty());
+    }
+
     #[test]
     fn get_an_item() {
         let mut buffer = Buffer::with_capacity(128);
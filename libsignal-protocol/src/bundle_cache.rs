@@ -0,0 +1,225 @@
+use crate::{
+    address::Address, bundle_fetcher::BundleFetcher, ids::DeviceId,
+    pre_key_bundle::PreKeyBundle,
+};
+use failure::Error;
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug, Formatter},
+    time::{Duration, Instant},
+};
+
+/// A cache of recently fetched [`PreKeyBundle`]s, keyed by [`Address`].
+///
+/// Sending several messages to a new contact in quick succession would
+/// otherwise mean a round trip to the bundle endpoint for each of them.
+/// Cached bundles expire after a configurable time-to-live.
+///
+/// A one-time pre-key may only be used once, so only the first lookup for
+/// an address gets the bundle's one-time pre-key. Later lookups get the
+/// same bundle with the one-time pre-key removed, falling back to the
+/// signed pre-key.
+pub struct BundleCache {
+    ttl: Duration,
+    entries: HashMap<(Vec<u8>, DeviceId), Entry>,
+}
+
+struct Entry {
+    bundle: PreKeyBundle,
+    fetched_at: Instant,
+    pre_key_used: bool,
+}
+
+impl BundleCache {
+    /// Create an empty cache where bundles are kept for `ttl`.
+    pub fn new(ttl: Duration) -> BundleCache {
+        BundleCache {
+            ttl,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Add a freshly fetched bundle to the cache, replacing any previous
+    /// bundle for that address.
+    pub fn insert(&mut self, address: &Address, bundle: PreKeyBundle) {
+        self.insert_at(address, bundle, Instant::now());
+    }
+
+    /// Look up the cached bundle for an address, if there is one and it
+    /// hasn't expired.
+    pub fn get(
+        &mut self,
+        address: &Address,
+    ) -> Result<Option<PreKeyBundle>, Error> {
+        self.get_at(address, Instant::now())
+    }
+
+    /// Look up the bundle for an address, using the [`BundleFetcher`] to
+    /// retrieve (and cache) a new one if necessary.
+    pub fn get_or_fetch<F: BundleFetcher>(
+        &mut self,
+        address: &Address,
+        fetcher: &F,
+    ) -> Result<PreKeyBundle, Error> {
+        if let Some(bundle) = self.get(address)? {
+            return Ok(bundle);
+        }
+
+        let bundle = fetcher.fetch(address.as_str()?, address.device_id())?;
+        self.insert(address, bundle);
+
+        self.get(address)?.ok_or_else(|| {
+            failure::err_msg("The freshly fetched bundle has already expired")
+        })
+    }
+
+    /// Forget the bundle for a particular address.
+    pub fn remove(&mut self, address: &Address) {
+        self.entries.remove(&key(address));
+    }
+
+    /// Remove all expired bundles from the cache.
+    pub fn purge_expired(&mut self) { self.purge_expired_at(Instant::now()); }
+
+    pub fn len(&self) -> usize { self.entries.len() }
+
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+    fn insert_at(
+        &mut self,
+        address: &Address,
+        bundle: PreKeyBundle,
+        now: Instant,
+    ) {
+        let entry = Entry {
+            bundle,
+            fetched_at: now,
+            pre_key_used: false,
+        };
+        self.entries.insert(key(address), entry);
+    }
+
+    fn get_at(
+        &mut self,
+        address: &Address,
+        now: Instant,
+    ) -> Result<Option<PreKeyBundle>, Error> {
+        let key = key(address);
+
+        let expired = match self.entries.get(&key) {
+            Some(entry) => now.duration_since(entry.fetched_at) >= self.ttl,
+            None => return Ok(None),
+        };
+
+        if expired {
+            self.entries.remove(&key);
+            return Ok(None);
+        }
+
+        let entry = self.entries.get_mut(&key).expect("checked above");
+
+        if entry.pre_key_used {
+            entry.bundle.without_pre_key().map(Some)
+        } else {
+            entry.pre_key_used = true;
+            Ok(Some(entry.bundle.clone()))
+        }
+    }
+
+    fn purge_expired_at(&mut self, now: Instant) {
+        let ttl = self.ttl;
+        self.entries
+            .retain(|_, entry| now.duration_since(entry.fetched_at) < ttl);
+    }
+}
+
+impl Debug for BundleCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BundleCache")
+            .field("ttl", &self.ttl)
+            .field("len", &self.entries.len())
+            .finish()
+    }
+}
+
+fn key(address: &Address) -> (Vec<u8>, DeviceId) {
+    (address.bytes().to_vec(), address.device_id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ids::{PreKeyId, SignedPreKeyId},
+        Context,
+    };
+    use std::cell::Cell;
+
+    fn device(id: u32) -> DeviceId { DeviceId::new(id).unwrap() }
+
+    fn bundle(ctx: &Context) -> PreKeyBundle {
+        let key = ctx.generate_key_pair().unwrap().public().unwrap();
+
+        PreKeyBundle::builder()
+            .registration_id(42)
+            .device_id(device(1))
+            .pre_key(PreKeyId::new(7), &key)
+            .signed_pre_key(SignedPreKeyId::new(2), &key)
+            .signature(&[0; 64])
+            .identity_key(&key)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn the_one_time_pre_key_is_only_handed_out_once() {
+        let ctx = Context::default();
+        let address = Address::new("+14159998888", device(1));
+        let mut cache = BundleCache::new(Duration::from_secs(60));
+        cache.insert(&address, bundle(&ctx));
+
+        let first = cache.get(&address).unwrap().unwrap();
+        let second = cache.get(&address).unwrap().unwrap();
+
+        assert_eq!(first.pre_key_id(), Some(PreKeyId::new(7)));
+        assert_eq!(second.pre_key_id(), None);
+        assert_eq!(second.signed_pre_key_id(), first.signed_pre_key_id());
+    }
+
+    #[test]
+    fn bundles_expire() {
+        let ctx = Context::default();
+        let address = Address::new("+14159998888", device(1));
+        let other = Address::new("+14159998888", device(2));
+        let mut cache = BundleCache::new(Duration::from_secs(60));
+        let start = Instant::now();
+        cache.insert_at(&address, bundle(&ctx), start);
+        cache.insert_at(&other, bundle(&ctx), start + Duration::from_secs(30));
+
+        let later = start + Duration::from_secs(61);
+        assert!(cache.get_at(&address, later).unwrap().is_none());
+        assert!(cache.get_at(&other, later).unwrap().is_some());
+
+        cache.purge_expired_at(start + Duration::from_secs(120));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn only_fetch_when_nothing_is_cached() {
+        let ctx = Context::default();
+        let address = Address::new("+14159998888", device(1));
+        let mut cache = BundleCache::new(Duration::from_secs(60));
+        let fetches = Cell::new(0);
+        let fetcher = |name: &str, device_id: DeviceId| {
+            assert_eq!(name, "+14159998888");
+            assert_eq!(device_id, device(1));
+            fetches.set(fetches.get() + 1);
+            Ok(bundle(&ctx))
+        };
+
+        cache.get_or_fetch(&address, &fetcher).unwrap();
+        cache.get_or_fetch(&address, &fetcher).unwrap();
+
+        assert_eq!(fetches.get(), 1);
+    }
+}
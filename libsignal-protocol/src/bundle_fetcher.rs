@@ -0,0 +1,33 @@
+use crate::{ids::DeviceId, pre_key_bundle::PreKeyBundle};
+use failure::Error;
+
+/// Something which can retrieve a recipient's [`PreKeyBundle`] from a server.
+///
+/// This is the transport-agnostic half of the "no session yet, fetch a
+/// bundle, build a session" flow. The protocol doesn't care how bundles are
+/// obtained, so applications implement this for whatever server they talk
+/// to.
+///
+/// Any closure with the right signature can be used as a fetcher.
+pub trait BundleFetcher {
+    /// Fetch the [`PreKeyBundle`] for a particular device belonging to
+    /// `name`.
+    fn fetch(
+        &self,
+        name: &str,
+        device_id: DeviceId,
+    ) -> Result<PreKeyBundle, Error>;
+}
+
+impl<F> BundleFetcher for F
+where
+    F: Fn(&str, DeviceId) -> Result<PreKeyBundle, Error>,
+{
+    fn fetch(
+        &self,
+        name: &str,
+        device_id: DeviceId,
+    ) -> Result<PreKeyBundle, Error> {
+        self(name, device_id)
+    }
+}
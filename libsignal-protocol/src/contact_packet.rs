@@ -0,0 +1,270 @@
+use crate::{
+    errors::InternalError,
+    ids::{DeviceId, PreKeyId, SignedPreKeyId},
+    keys::{IdentityKeyPair, PreKey, PublicKey, SessionSignedPreKey},
+    pre_key_bundle::PreKeyBundle,
+    session_builder::SessionBuilder,
+};
+use failure::Error;
+use std::convert::{TryFrom, TryInto};
+
+const FORMAT_VERSION: u8 = 1;
+/// A serialized curve25519 public key, including the key type byte.
+const PUBLIC_KEY_LEN: usize = 33;
+const SIGNATURE_LEN: usize = sys::CURVE_SIGNATURE_LEN as usize;
+/// Everything up to and including the signed pre-key's signature.
+const REQUIRED_LEN: usize =
+    1 + 4 + 4 + PUBLIC_KEY_LEN + 4 + PUBLIC_KEY_LEN + SIGNATURE_LEN;
+const PRE_KEY_LEN: usize = 4 + PUBLIC_KEY_LEN;
+
+/// A self-contained bundle of everything a peer needs to start a session
+/// with us, for exchanging keys without a server (e.g. as a QR code or over
+/// a local network).
+///
+/// The packet is a compact binary encoding of a [`PreKeyBundle`]:
+///
+/// ```text
+/// version (1) || registration ID (4) || device ID (4) || identity key (33)
+///     || signed pre-key ID (4) || signed pre-key (33) || signature (64)
+///     || [pre-key ID (4) || pre-key (33)]
+/// ```
+///
+/// Integers are big-endian and the one-time pre-key is optional, so a packet
+/// is either 143 or 180 bytes long.
+#[derive(Debug, Clone)]
+pub struct ContactPacket {
+    bundle: PreKeyBundle,
+}
+
+impl ContactPacket {
+    /// Assemble a packet from our own identity and pre-keys.
+    ///
+    /// The one-time pre-key should be removed from the [`PreKeyStore`] once
+    /// the peer has used it, just like one handed out by a server.
+    ///
+    /// [`PreKeyStore`]: crate::PreKeyStore
+    pub fn new(
+        registration_id: u32,
+        device_id: DeviceId,
+        identity_key_pair: &IdentityKeyPair,
+        signed_pre_key: &SessionSignedPreKey,
+        pre_key: Option<&PreKey>,
+    ) -> Result<ContactPacket, Error> {
+        let mut builder = PreKeyBundle::builder()
+            .registration_id(registration_id)
+            .device_id(device_id)
+            .identity_key(&identity_key_pair.public_key()?)
+            .signed_pre_key(
+                signed_pre_key.id(),
+                &signed_pre_key.get_key_pair().public()?,
+            )
+            .signature(signed_pre_key.get_signature());
+
+        if let Some(pre_key) = pre_key {
+            builder =
+                builder.pre_key(pre_key.id(), &pre_key.key_pair().public()?);
+        }
+
+        Ok(ContactPacket {
+            bundle: builder.build()?,
+        })
+    }
+
+    /// Parse a packet received from a peer, checking the signed pre-key was
+    /// signed by their identity key.
+    pub fn from_bytes(bytes: &[u8]) -> Result<ContactPacket, Error> {
+        if bytes.len() != REQUIRED_LEN
+            && bytes.len() != REQUIRED_LEN + PRE_KEY_LEN
+        {
+            return Err(InternalError::InvalidMessage.into());
+        }
+        if bytes[0] != FORMAT_VERSION {
+            return Err(InternalError::InvalidVersion.into());
+        }
+
+        let mut rest = &bytes[1..];
+        let mut builder = PreKeyBundle::builder()
+            .registration_id(read_u32(&mut rest))
+            .device_id(DeviceId::new(read_u32(&mut rest))?)
+            .identity_key(&read_key(&mut rest)?);
+        let signed_pre_key_id = SignedPreKeyId::new(read_u32(&mut rest));
+        builder = builder
+            .signed_pre_key(signed_pre_key_id, &read_key(&mut rest)?)
+            .signature(take(&mut rest, SIGNATURE_LEN));
+
+        if !rest.is_empty() {
+            let pre_key_id = PreKeyId::new(read_u32(&mut rest));
+            builder = builder.pre_key(pre_key_id, &read_key(&mut rest)?);
+        }
+
+        let bundle = builder.build()?;
+        bundle.verify()?;
+
+        Ok(ContactPacket { bundle })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let bundle = &self.bundle;
+        let mut bytes = Vec::with_capacity(REQUIRED_LEN + PRE_KEY_LEN);
+
+        bytes.push(FORMAT_VERSION);
+        bytes.extend_from_slice(&bundle.registration_id().to_be_bytes());
+        bytes.extend_from_slice(&bundle.device_id().value().to_be_bytes());
+        bundle.identity_key().serialize(&mut bytes)?;
+        bytes.extend_from_slice(
+            &bundle.signed_pre_key_id().value().to_be_bytes(),
+        );
+        bundle.signed_pre_key().serialize(&mut bytes)?;
+        bytes.extend_from_slice(bundle.signed_pre_key_signature());
+
+        if let (Some(id), Some(pre_key)) =
+            (bundle.pre_key_id(), bundle.pre_key())
+        {
+            bytes.extend_from_slice(&id.value().to_be_bytes());
+            pre_key.serialize(&mut bytes)?;
+        }
+
+        Ok(bytes)
+    }
+
+    pub fn bundle(&self) -> &PreKeyBundle { &self.bundle }
+
+    pub fn into_bundle(self) -> PreKeyBundle { self.bundle }
+}
+
+impl From<PreKeyBundle> for ContactPacket {
+    fn from(bundle: PreKeyBundle) -> ContactPacket { ContactPacket { bundle } }
+}
+
+impl SessionBuilder {
+    /// Establish a session with the peer who sent us a [`ContactPacket`].
+    pub fn process_contact_packet(
+        &self,
+        packet: &ContactPacket,
+    ) -> Result<(), Error> {
+        packet.bundle().verify()?;
+        self.process_pre_key_bundle(packet.bundle())
+    }
+}
+
+/// Split off the next `len` bytes. The caller has already checked there are
+/// enough.
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> &'a [u8] {
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    head
+}
+
+fn read_u32(bytes: &mut &[u8]) -> u32 {
+    u32::from_be_bytes(take(bytes, 4).try_into().unwrap())
+}
+
+fn read_key(bytes: &mut &[u8]) -> Result<PublicKey, Error> {
+    PublicKey::try_from(take(bytes, PUBLIC_KEY_LEN))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pre_key_bundle::PreKeyBundleError, Context, Timestamp};
+
+    fn packet(ctx: &Context, with_pre_key: bool) -> ContactPacket {
+        let public = || ctx.generate_key_pair().unwrap().public().unwrap();
+        let mut builder = PreKeyBundle::builder()
+            .registration_id(42)
+            .device_id(DeviceId::new(3).unwrap())
+            .identity_key(&public())
+            .signed_pre_key(SignedPreKeyId::new(7), &public())
+            .signature(&[0xab; SIGNATURE_LEN]);
+
+        if with_pre_key {
+            builder = builder.pre_key(PreKeyId::new(99), &public());
+        }
+
+        ContactPacket::from(builder.build().unwrap())
+    }
+
+    fn signed_packet(ctx: &Context) -> ContactPacket {
+        let identity = ctx.generate_identity_key_pair().unwrap();
+        let signed_pre_key = ctx
+            .generate_signed_pre_key(
+                &identity,
+                SignedPreKeyId::new(7),
+                Timestamp::now(),
+            )
+            .unwrap();
+        let pre_key = ctx
+            .generate_pre_keys(PreKeyId::new(99), 1)
+            .unwrap()
+            .iter()
+            .next()
+            .unwrap();
+
+        ContactPacket::new(
+            42,
+            DeviceId::new(3).unwrap(),
+            &identity,
+            &signed_pre_key,
+            Some(&pre_key),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn packets_have_a_fixed_length() {
+        let ctx = Context::default();
+
+        assert_eq!(packet(&ctx, false).to_bytes().unwrap().len(), 143);
+        assert_eq!(packet(&ctx, true).to_bytes().unwrap().len(), 180);
+    }
+
+    #[test]
+    fn malformed_packets_are_rejected() {
+        let ctx = Context::default();
+        let mut bytes = packet(&ctx, true).to_bytes().unwrap();
+
+        let err = ContactPacket::from_bytes(&bytes[..150]).err().unwrap();
+        assert_eq!(
+            err.downcast_ref::<InternalError>(),
+            Some(&InternalError::InvalidMessage)
+        );
+
+        bytes[0] = FORMAT_VERSION + 1;
+        let err = ContactPacket::from_bytes(&bytes).err().unwrap();
+        assert_eq!(
+            err.downcast_ref::<InternalError>(),
+            Some(&InternalError::InvalidVersion)
+        );
+    }
+
+    #[test]
+    fn signed_packets_round_trip() {
+        let ctx = Context::default();
+        let original = signed_packet(&ctx);
+        let bytes = original.to_bytes().unwrap();
+
+        let got = ContactPacket::from_bytes(&bytes).unwrap();
+
+        assert_eq!(got.to_bytes().unwrap(), bytes);
+        let bundle = got.bundle();
+        assert_eq!(bundle.registration_id(), 42);
+        assert_eq!(bundle.device_id(), DeviceId::new(3).unwrap());
+        assert_eq!(bundle.signed_pre_key_id(), SignedPreKeyId::new(7));
+        assert_eq!(bundle.pre_key_id(), Some(PreKeyId::new(99)));
+    }
+
+    #[test]
+    fn tampered_signatures_are_rejected() {
+        let ctx = Context::default();
+        let mut bytes = signed_packet(&ctx).to_bytes().unwrap();
+        let signature_start = REQUIRED_LEN - SIGNATURE_LEN;
+        bytes[signature_start] ^= 0x01;
+
+        let err = ContactPacket::from_bytes(&bytes).err().unwrap();
+
+        assert_eq!(
+            err.downcast_ref::<PreKeyBundleError>(),
+            Some(&PreKeyBundleError::InvalidSignature)
+        );
+    }
+}
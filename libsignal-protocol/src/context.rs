@@ -8,7 +8,6 @@ use std::{
     pin::Pin,
     ptr,
     rc::Rc,
-    time::SystemTime,
 };
 
 #[cfg(feature = "crypto-native")]
@@ -18,17 +17,24 @@ use crate::{
     errors::{FromInternalErrorCode, InternalError},
     hkdf::HMACBasedKeyDerivationFunction,
     identity_key_store::{self as iks, IdentityKeyStore},
+    ids::{PreKeyId, SignedPreKeyId},
     keys::{
-        IdentityKeyPair, KeyPair, PreKeyList, PrivateKey, SessionSignedPreKey,
+        IdentityKeyPair, KeyPair, PreKeyList, PreKeyRecord, PrivateKey,
+        SessionSignedPreKey,
     },
     pre_key_store::{self as pks, PreKeyStore},
     raw_ptr::Raw,
     session_store::{self as sess, SessionStore},
     signed_pre_key_store::{self as spks, SignedPreKeyStore},
+    timestamp::Timestamp,
     Buffer, StoreContext,
 };
 
 /// Global state and callbacks used by the library.
+///
+/// Cloning a [`Context`] is cheap, all clones share the same underlying
+/// `signal_context`.
+#[derive(Debug, Clone)]
 pub struct Context(pub(crate) Rc<ContextInner>);
 
 impl Context {
@@ -103,14 +109,14 @@ impl Context {
 
     pub fn generate_pre_keys(
         &self,
-        start: u32,
+        start: PreKeyId,
         count: u32,
     ) -> Result<PreKeyList, Error> {
         unsafe {
             let mut pre_keys_head = ptr::null_mut();
             sys::signal_protocol_key_helper_generate_pre_keys(
                 &mut pre_keys_head,
-                start,
+                start.value(),
                 count,
                 self.raw(),
             )
@@ -120,21 +126,34 @@ impl Context {
         }
     }
 
+    /// Generate a batch of pre-keys, like [`Context::generate_pre_keys()`],
+    /// but collected into owned [`PreKeyRecord`]s which already contain the
+    /// bytes a [`PreKeyStore`] should save.
+    pub fn generate_pre_key_records(
+        &self,
+        start: PreKeyId,
+        count: u32,
+    ) -> Result<Vec<PreKeyRecord>, Error> {
+        self.generate_pre_keys(start, count)?
+            .iter()
+            .map(PreKeyRecord::new)
+            .collect()
+    }
+
     pub fn generate_signed_pre_key(
         &self,
         identity_key_pair: &IdentityKeyPair,
-        id: u32,
-        timestamp: SystemTime,
+        id: SignedPreKeyId,
+        timestamp: Timestamp,
     ) -> Result<SessionSignedPreKey, Error> {
         unsafe {
             let mut raw = ptr::null_mut();
-            let unix_time = timestamp.duration_since(SystemTime::UNIX_EPOCH)?;
 
             sys::signal_protocol_key_helper_generate_signed_pre_key(
                 &mut raw,
                 identity_key_pair.raw.as_const_ptr(),
-                id,
-                unix_time.as_secs(),
+                id.value(),
+                timestamp.as_millis(),
                 self.raw(),
             )
             .into_result()?;
@@ -211,6 +230,15 @@ impl Context {
 
     pub fn crypto(&self) -> &dyn Crypto { self.0.crypto.state() }
 
+    /// Fill a buffer with cryptographically secure random bytes.
+    ///
+    /// This uses the same [`Crypto`] provider as the protocol itself, so
+    /// nonces, attachment keys and group IDs come from the same entropy
+    /// source as everything else.
+    pub fn random_bytes(&self, buffer: &mut [u8]) -> Result<(), Error> {
+        self.crypto().fill_random(buffer).map_err(Error::from)
+    }
+
     pub(crate) fn raw(&self) -> *mut sys::signal_context { self.0.raw() }
 }
 
@@ -326,4 +354,17 @@ mod tests {
 
         drop(ctx);
     }
+
+    #[test]
+    fn random_bytes_fill_the_whole_buffer() {
+        let ctx = Context::default();
+        let mut first = [0; 32];
+        let mut second = [0; 32];
+
+        ctx.random_bytes(&mut first).unwrap();
+        ctx.random_bytes(&mut second).unwrap();
+
+        assert_ne!(first, [0; 32]);
+        assert_ne!(first, second);
+    }
 }
@@ -0,0 +1,391 @@
+use crate::{
+    crypto::{
+        Crypto, Sha256Digest, Sha256Hmac, Sha512Digest, SignalCipherType,
+    },
+    errors::InternalError,
+};
+use std::{
+    cell::RefCell,
+    fmt::{self, Debug, Formatter},
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+/// Somewhere raw entropy can be read from (e.g. a hardware TRNG).
+pub trait EntropySource {
+    fn fill(&mut self, buffer: &mut [u8]) -> io::Result<()>;
+}
+
+/// An [`EntropySource`] which reads from a device file such as
+/// `/dev/hwrng`.
+#[derive(Debug)]
+pub struct DeviceEntropy {
+    device: File,
+}
+
+impl DeviceEntropy {
+    /// The Linux hardware RNG device.
+    pub const HWRNG: &'static str = "/dev/hwrng";
+
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<DeviceEntropy> {
+        File::open(path).map(|device| DeviceEntropy { device })
+    }
+}
+
+impl EntropySource for DeviceEntropy {
+    fn fill(&mut self, buffer: &mut [u8]) -> io::Result<()> {
+        self.device.read_exact(buffer)
+    }
+}
+
+/// What to do when the hardware source fails to produce data or fails one of
+/// its health tests.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FallbackPolicy {
+    /// Report the failure, aborting whatever operation needed randomness.
+    Fail,
+    /// Use the wrapped [`Crypto`] provider's RNG instead.
+    UseFallback,
+}
+
+/// Cutoffs for the continuous health tests from NIST SP 800-90B, section
+/// 4.4.
+///
+/// Samples are individual bytes. The defaults assume a conservative
+/// min-entropy of 1 bit per byte with a false positive rate of 2^-20.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HealthTests {
+    /// Fail once the same byte has been seen this many times in a row.
+    pub repetition_cutoff: usize,
+    /// The number of samples in each adaptive proportion test window.
+    pub proportion_window: usize,
+    /// Fail once the first byte of a window has been seen this many times
+    /// within it.
+    pub proportion_cutoff: usize,
+}
+
+impl Default for HealthTests {
+    fn default() -> HealthTests {
+        HealthTests {
+            repetition_cutoff: 21,
+            proportion_window: 512,
+            proportion_cutoff: 410,
+        }
+    }
+}
+
+/// A [`Crypto`] provider which gets its randomness from an
+/// [`EntropySource`], checking it with continuous health tests.
+///
+/// Everything except [`Crypto::fill_random()`] is delegated to the wrapped
+/// provider, whose RNG is also used as a fallback when the
+/// [`FallbackPolicy`] allows it. Once a health test fails the hardware
+/// source is considered broken and is never used again.
+pub struct HardwareRngCrypto<C, S> {
+    inner: C,
+    source: RefCell<S>,
+    monitor: RefCell<HealthMonitor>,
+    policy: FallbackPolicy,
+}
+
+impl<C: Crypto, S: EntropySource> HardwareRngCrypto<C, S> {
+    /// Take randomness from `source`, checking it against the provided
+    /// [`HealthTests`].
+    ///
+    /// # Panics
+    ///
+    /// The [`HealthTests`] must be usable. Both cutoffs need to be at least
+    /// 2, otherwise every sample would fail, and the `proportion_window` can't
+    /// be empty.
+    pub fn new(
+        inner: C,
+        source: S,
+        tests: HealthTests,
+        policy: FallbackPolicy,
+    ) -> HardwareRngCrypto<C, S> {
+        assert!(
+            tests.repetition_cutoff > 1,
+            "The repetition cutoff must be at least 2"
+        );
+        assert!(
+            tests.proportion_cutoff > 1,
+            "The proportion cutoff must be at least 2"
+        );
+        assert!(
+            tests.proportion_window > 0,
+            "The proportion window can't be empty"
+        );
+
+        HardwareRngCrypto {
+            inner,
+            source: RefCell::new(source),
+            monitor: RefCell::new(HealthMonitor::new(tests)),
+            policy,
+        }
+    }
+
+    /// Has the hardware source failed a health test?
+    pub fn is_failed(&self) -> bool { self.monitor.borrow().failed }
+
+    fn fill_from_source(&self, buffer: &mut [u8]) -> Result<(), ()> {
+        let mut monitor = self.monitor.borrow_mut();
+        if monitor.failed {
+            return Err(());
+        }
+
+        self.source.borrow_mut().fill(buffer).map_err(|_| ())?;
+
+        if buffer.iter().all(|&sample| monitor.check(sample)) {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+}
+
+impl<C: Crypto, S: EntropySource> Crypto for HardwareRngCrypto<C, S> {
+    fn fill_random(&self, buffer: &mut [u8]) -> Result<(), InternalError> {
+        match (self.fill_from_source(buffer), self.policy) {
+            (Ok(()), _) => Ok(()),
+            (Err(()), FallbackPolicy::UseFallback) => {
+                self.inner.fill_random(buffer)
+            },
+            (Err(()), FallbackPolicy::Fail) => {
+                // don't leave possibly-bad entropy lying around
+                for byte in buffer.iter_mut() {
+                    *byte = 0;
+                }
+                Err(InternalError::Unknown)
+            },
+        }
+    }
+
+    fn hmac_sha256(
+        &self,
+        key: &[u8],
+    ) -> Result<Box<dyn Sha256Hmac>, InternalError> {
+        self.inner.hmac_sha256(key)
+    }
+
+    fn sha256_digest(&self) -> Result<Box<dyn Sha256Digest>, InternalError> {
+        self.inner.sha256_digest()
+    }
+
+    fn sha512_digest(&self) -> Result<Box<dyn Sha512Digest>, InternalError> {
+        self.inner.sha512_digest()
+    }
+
+    fn encrypt(
+        &self,
+        cipher: SignalCipherType,
+        key: &[u8],
+        iv: &[u8],
+        data: &[u8],
+    ) -> Result<Vec<u8>, InternalError> {
+        self.inner.encrypt(cipher, key, iv, data)
+    }
+
+    fn decrypt(
+        &self,
+        cipher: SignalCipherType,
+        key: &[u8],
+        iv: &[u8],
+        data: &[u8],
+    ) -> Result<Vec<u8>, InternalError> {
+        self.inner.decrypt(cipher, key, iv, data)
+    }
+}
+
+impl<C: Debug, S> Debug for HardwareRngCrypto<C, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HardwareRngCrypto")
+            .field("inner", &self.inner)
+            .field("policy", &self.policy)
+            .field("failed", &self.monitor.borrow().failed)
+            .finish()
+    }
+}
+
+/// State for the repetition count and adaptive proportion tests.
+#[derive(Debug)]
+struct HealthMonitor {
+    tests: HealthTests,
+    failed: bool,
+    last: Option<u8>,
+    repetitions: usize,
+    window_sample: u8,
+    window_seen: usize,
+    window_matches: usize,
+}
+
+impl HealthMonitor {
+    fn new(tests: HealthTests) -> HealthMonitor {
+        HealthMonitor {
+            tests,
+            failed: false,
+            last: None,
+            repetitions: 0,
+            window_sample: 0,
+            window_seen: 0,
+            window_matches: 0,
+        }
+    }
+
+    /// Feed a sample through both tests, returning `false` (and latching the
+    /// failure) if either of them fails.
+    fn check(&mut self, sample: u8) -> bool {
+        if self.last == Some(sample) {
+            self.repetitions += 1;
+        } else {
+            self.last = Some(sample);
+            self.repetitions = 1;
+        }
+
+        if self.window_seen == 0 {
+            self.window_sample = sample;
+            self.window_matches = 1;
+        } else if sample == self.window_sample {
+            self.window_matches += 1;
+        }
+        self.window_seen =
+            (self.window_seen + 1) % self.tests.proportion_window;
+
+        if self.repetitions >= self.tests.repetition_cutoff
+            || self.window_matches >= self.tests.proportion_cutoff
+        {
+            self.failed = true;
+        }
+
+        !self.failed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::DefaultCrypto;
+
+    struct Constant(u8);
+
+    impl EntropySource for Constant {
+        fn fill(&mut self, buffer: &mut [u8]) -> io::Result<()> {
+            for byte in buffer.iter_mut() {
+                *byte = self.0;
+            }
+            Ok(())
+        }
+    }
+
+    struct Counter(u8);
+
+    impl EntropySource for Counter {
+        fn fill(&mut self, buffer: &mut [u8]) -> io::Result<()> {
+            for byte in buffer.iter_mut() {
+                *byte = self.0;
+                self.0 = self.0.wrapping_add(1);
+            }
+            Ok(())
+        }
+    }
+
+    struct Broken;
+
+    impl EntropySource for Broken {
+        // io::Error::other() is newer than our minimum supported Rust version
+        #[allow(clippy::io_other_error)]
+        fn fill(&mut self, _buffer: &mut [u8]) -> io::Result<()> {
+            Err(io::Error::new(io::ErrorKind::Other, "unplugged"))
+        }
+    }
+
+    #[test]
+    fn healthy_sources_are_used() {
+        let crypto = HardwareRngCrypto::new(
+            DefaultCrypto,
+            Counter(0),
+            HealthTests::default(),
+            FallbackPolicy::Fail,
+        );
+        let mut buffer = [0; 4];
+
+        crypto.fill_random(&mut buffer).unwrap();
+
+        assert_eq!(buffer, [0, 1, 2, 3]);
+        assert!(!crypto.is_failed());
+    }
+
+    #[test]
+    fn stuck_sources_fail_the_repetition_test() {
+        let crypto = HardwareRngCrypto::new(
+            DefaultCrypto,
+            Constant(0xAA),
+            HealthTests::default(),
+            FallbackPolicy::Fail,
+        );
+        let mut buffer = [0; 64];
+
+        assert!(crypto.fill_random(&mut buffer).is_err());
+        assert!(crypto.is_failed());
+        assert_eq!(buffer, [0; 64]);
+    }
+
+    #[test]
+    fn biased_sources_fail_the_proportion_test() {
+        let mut monitor = HealthMonitor::new(HealthTests::default());
+
+        // break up the runs so the repetition test never trips
+        let healthy =
+            (0..512).all(|i| monitor.check(if i % 8 == 7 { 7 } else { 0 }));
+
+        assert!(!healthy);
+        assert!(monitor.failed);
+    }
+
+    #[test]
+    #[should_panic(expected = "The proportion window can't be empty")]
+    fn empty_proportion_windows_are_rejected() {
+        let tests = HealthTests {
+            proportion_window: 0,
+            ..HealthTests::default()
+        };
+
+        HardwareRngCrypto::new(
+            DefaultCrypto,
+            Counter(0),
+            tests,
+            FallbackPolicy::Fail,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The repetition cutoff must be at least 2")]
+    fn cutoffs_which_fail_every_sample_are_rejected() {
+        let tests = HealthTests {
+            repetition_cutoff: 0,
+            ..HealthTests::default()
+        };
+
+        HardwareRngCrypto::new(
+            DefaultCrypto,
+            Counter(0),
+            tests,
+            FallbackPolicy::Fail,
+        );
+    }
+
+    #[test]
+    fn fall_back_when_allowed() {
+        let crypto = HardwareRngCrypto::new(
+            DefaultCrypto,
+            Broken,
+            HealthTests::default(),
+            FallbackPolicy::UseFallback,
+        );
+        let mut buffer = [0; 32];
+
+        crypto.fill_random(&mut buffer).unwrap();
+
+        assert_ne!(buffer, [0; 32]);
+    }
+}
@@ -3,6 +3,14 @@ mod native;
 #[cfg(feature = "crypto-native")]
 pub use self::native::DefaultCrypto;
 
+#[cfg(feature = "crypto-hwrng")]
+mod hwrng;
+#[cfg(feature = "crypto-hwrng")]
+pub use self::hwrng::{
+    DeviceEntropy, EntropySource, FallbackPolicy, HardwareRngCrypto,
+    HealthTests,
+};
+
 #[cfg(feature = "crypto-openssl")]
 mod openssl;
 #[cfg(feature = "crypto-openssl")]
@@ -25,11 +33,12 @@ use crate::{
 #[derive(Debug, Clone)]
 pub struct SignalCipherTypeError(i32);
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone)]
 pub enum CipherMode {
     Encrypt,
     Decrypt,
 }
+#[derive(Debug)]
 pub enum SignalCipherType {
     AesCtrNoPadding,
     AesCbcPkcs5,
@@ -56,6 +65,12 @@ pub trait Sha256Hmac {
     fn finalize(&mut self) -> Result<Vec<u8>, InternalError>;
 }
 
+/// Something which can generate a SHA-256 hash.
+pub trait Sha256Digest {
+    fn update(&mut self, data: &[u8]) -> Result<(), InternalError>;
+    fn finalize(&mut self) -> Result<Vec<u8>, InternalError>;
+}
+
 /// Something which can generate a SHA-512 hash.
 pub trait Sha512Digest {
     fn update(&mut self, data: &[u8]) -> Result<(), InternalError>;
@@ -73,6 +88,15 @@ pub trait Crypto {
         key: &[u8],
     ) -> Result<Box<dyn Sha256Hmac>, InternalError>;
 
+    /// Start to generate a SHA-256 digest.
+    ///
+    /// This isn't used by `libsignal-protocol-c` itself, only by helpers
+    /// like [`AttachmentKeys`](crate::AttachmentKeys), so providers which
+    /// don't support it can leave the default, which always fails.
+    fn sha256_digest(&self) -> Result<Box<dyn Sha256Digest>, InternalError> {
+        Err(InternalError::Unknown)
+    }
+
     /// Start to generate a SHA-512 digest.
     fn sha512_digest(&self) -> Result<Box<dyn Sha512Digest>, InternalError>;
 
@@ -1,5 +1,7 @@
 use crate::{
-    crypto::{Crypto, Sha256Hmac, Sha512Digest, SignalCipherType},
+    crypto::{
+        Crypto, Sha256Digest, Sha256Hmac, Sha512Digest, SignalCipherType,
+    },
     errors::{FromInternalErrorCode, InternalError, IntoInternalErrorCode},
 };
 use openssl::{
@@ -8,6 +10,7 @@ use openssl::{
     symm::{Cipher, Crypter, Mode},
 };
 
+#[derive(Debug)]
 pub struct OpenSSLCrypto;
 
 impl OpenSSLCrypto {
@@ -37,19 +40,22 @@ impl OpenSSLCrypto {
             _ => unreachable!(),
         };
 
+        // openssl writes into the slice it's given, so the output buffer
+        // needs to be big enough for the data plus one block of padding
         let block_size = signal_cipher_type.block_size();
-        let mut result = Vec::with_capacity(data.len() + block_size);
+        let mut result = vec![0; data.len() + block_size];
         let mut crypter = Crypter::new(signal_cipher_type, mode, key, Some(iv))
             .map_err(|_e| InternalError::Unknown)?;
 
-        crypter
+        let mut written = crypter
             .update(data, &mut result)
             .map_err(|_e| InternalError::Unknown)?;
 
-        crypter
-            .finalize(&mut result)
+        written += crypter
+            .finalize(&mut result[written..])
             .map_err(|_e| InternalError::Unknown)?;
 
+        result.truncate(written);
         Ok(result)
     }
 }
@@ -63,9 +69,11 @@ impl Crypto for OpenSSLCrypto {
         &self,
         key: &[u8],
     ) -> Result<Box<dyn Sha256Hmac>, InternalError> {
-        let nid = Nid::HMACWITHSHA256;
-        let ty = MessageDigest::from_nid(nid)
-            .ok_or_else(|| InternalError::Unknown)?;
+        Ok(Box::new(HmacSha256::new(key)?))
+    }
+
+    fn sha256_digest(&self) -> Result<Box<dyn Sha256Digest>, InternalError> {
+        let ty = MessageDigest::sha256();
         let hasher = Hasher::new(ty).map_err(|_e| InternalError::Unknown)?;
 
         Ok(Box::new(hasher))
@@ -160,7 +168,75 @@ impl Default for OpenSSLCrypto {
     fn default() -> OpenSSLCrypto { OpenSSLCrypto }
 }
 
-impl Sha256Hmac for Hasher {
+/// The SHA-256 block size, in bytes.
+const SHA256_BLOCK_SIZE: usize = 64;
+
+/// A streaming HMAC-SHA256 (RFC 2104) built on top of openssl's SHA-256
+/// [`Hasher`].
+struct HmacSha256 {
+    inner: Hasher,
+    key: [u8; SHA256_BLOCK_SIZE],
+}
+
+impl HmacSha256 {
+    fn new(key: &[u8]) -> Result<HmacSha256, InternalError> {
+        let mut padded_key = [0; SHA256_BLOCK_SIZE];
+
+        if key.len() > SHA256_BLOCK_SIZE {
+            let digest = openssl::hash::hash(MessageDigest::sha256(), key)
+                .map_err(|_| InternalError::Unknown)?;
+            padded_key[..digest.len()].copy_from_slice(&digest);
+        } else {
+            padded_key[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner = Hasher::new(MessageDigest::sha256())
+            .map_err(|_| InternalError::Unknown)?;
+        inner
+            .update(&xor_pad(&padded_key, 0x36))
+            .map_err(|_| InternalError::Unknown)?;
+
+        Ok(HmacSha256 {
+            inner,
+            key: padded_key,
+        })
+    }
+}
+
+fn xor_pad(key: &[u8; SHA256_BLOCK_SIZE], pad: u8) -> [u8; SHA256_BLOCK_SIZE] {
+    let mut padded = *key;
+    for byte in padded.iter_mut() {
+        *byte ^= pad;
+    }
+    padded
+}
+
+impl Sha256Hmac for HmacSha256 {
+    fn update(&mut self, data: &[u8]) -> Result<(), InternalError> {
+        self.inner.update(data).map_err(|_| InternalError::Unknown)
+    }
+
+    fn finalize(&mut self) -> Result<Vec<u8>, InternalError> {
+        let inner_hash =
+            self.inner.finish().map_err(|_| InternalError::Unknown)?;
+
+        let mut outer = Hasher::new(MessageDigest::sha256())
+            .map_err(|_| InternalError::Unknown)?;
+        outer
+            .update(&xor_pad(&self.key, 0x5c))
+            .map_err(|_| InternalError::Unknown)?;
+        outer
+            .update(&inner_hash)
+            .map_err(|_| InternalError::Unknown)?;
+
+        outer
+            .finish()
+            .map(|bytes| bytes.as_ref().to_vec())
+            .map_err(|_| InternalError::Unknown)
+    }
+}
+
+impl Sha256Digest for Hasher {
     fn update(&mut self, data: &[u8]) -> Result<(), InternalError> {
         self.update(data).map_err(|_| InternalError::Unknown)
     }
@@ -183,3 +259,59 @@ impl Sha512Digest for Hasher {
             .map_err(|_| InternalError::Unknown)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hmac_sha256_matches_rfc_4231() {
+        // test case 2 from RFC 4231
+        let mut hmac = OpenSSLCrypto.hmac_sha256(b"Jefe").unwrap();
+        hmac.update(b"what do ya want ").unwrap();
+        hmac.update(b"for nothing?").unwrap();
+        let got = hmac.finalize().unwrap();
+
+        let should_be = [
+            0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24,
+            0x26, 0x08, 0x95, 0x75, 0xc7, 0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27,
+            0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9, 0x64, 0xec, 0x38, 0x43,
+        ];
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn aes_cbc_round_trip() {
+        let key = [0x42; 32];
+        let iv = [0x24; 16];
+        let plaintext = b"Not a multiple of the block size";
+
+        let ciphertext = OpenSSLCrypto
+            .encrypt(SignalCipherType::AesCbcPkcs5, &key, &iv, plaintext)
+            .unwrap();
+        assert_eq!(ciphertext.len() % 16, 0);
+        assert!(ciphertext.len() > plaintext.len());
+
+        let decrypted = OpenSSLCrypto
+            .decrypt(SignalCipherType::AesCbcPkcs5, &key, &iv, &ciphertext)
+            .unwrap();
+        assert_eq!(decrypted, &plaintext[..]);
+    }
+
+    #[test]
+    fn aes_ctr_output_is_the_same_length_as_the_input() {
+        let key = [0x42; 16];
+        let iv = [0x24; 16];
+        let plaintext = b"Hello, World!";
+
+        let ciphertext = OpenSSLCrypto
+            .encrypt(SignalCipherType::AesCtrNoPadding, &key, &iv, plaintext)
+            .unwrap();
+        assert_eq!(ciphertext.len(), plaintext.len());
+
+        let decrypted = OpenSSLCrypto
+            .decrypt(SignalCipherType::AesCtrNoPadding, &key, &iv, &ciphertext)
+            .unwrap();
+        assert_eq!(decrypted, &plaintext[..]);
+    }
+}
@@ -0,0 +1,324 @@
+//! Checking that all of a user's linked devices agree on their identity keys.
+//!
+//! Each device builds a [`DeviceConsistencyCommitment`] from the identity
+//! keys of every device it knows about, signs it to create a
+//! [`DeviceConsistencyMessage`], and sends that to the other devices. Once a
+//! device has collected everyone's signatures it can generate a short
+//! [`DeviceConsistencyCommitment::code()`], which matches on every device
+//! if (and only if) they all have the same view of the identity keys.
+
+use crate::{
+    errors::FromInternalErrorCode,
+    keys::{IdentityKeyPair, KeyPair, PublicKey},
+    raw_ptr::Raw,
+    Context,
+};
+use failure::Error;
+use std::{
+    ffi::CStr,
+    fmt::{self, Debug, Formatter},
+    os::raw::{c_char, c_void},
+    ptr,
+};
+
+extern "C" {
+    // `device_consistency_code_generate_for()` allocates the code string with
+    // malloc()
+    fn free(ptr: *mut c_void);
+}
+
+/// A commitment to the identity keys of every device in a generation.
+#[derive(Clone)]
+pub struct DeviceConsistencyCommitment {
+    raw: Raw<sys::device_consistency_commitment>,
+}
+
+impl DeviceConsistencyCommitment {
+    /// Commit to a set of identity keys. The order of the keys doesn't
+    /// matter.
+    pub fn new(
+        ctx: &Context,
+        generation: u32,
+        identity_keys: &[PublicKey],
+    ) -> Result<DeviceConsistencyCommitment, Error> {
+        unsafe {
+            let list = sys::ec_public_key_list_alloc();
+            assert!(!list.is_null());
+
+            for key in identity_keys {
+                let result =
+                    sys::ec_public_key_list_push_back(list, key.raw.as_ptr());
+                if let Err(e) = result.into_result() {
+                    sys::ec_public_key_list_free(list);
+                    return Err(e.into());
+                }
+            }
+
+            let mut raw = ptr::null_mut();
+            let result = sys::device_consistency_commitment_create(
+                &mut raw,
+                generation,
+                list,
+                ctx.raw(),
+            );
+            sys::ec_public_key_list_free(list);
+            result.into_result()?;
+
+            Ok(DeviceConsistencyCommitment {
+                raw: Raw::from_ptr(raw),
+            })
+        }
+    }
+
+    pub fn generation(&self) -> u32 {
+        unsafe {
+            sys::device_consistency_commitment_get_generation(
+                self.raw.as_const_ptr(),
+            )
+        }
+    }
+
+    pub fn serialized(&self) -> &[u8] {
+        unsafe {
+            buffer_contents(sys::device_consistency_commitment_get_serialized(
+                self.raw.as_const_ptr(),
+            ))
+        }
+    }
+
+    /// Generate the code users compare across their devices, from the
+    /// signatures every device made over this commitment.
+    pub fn code(
+        &self,
+        ctx: &Context,
+        signatures: &[DeviceConsistencySignature],
+    ) -> Result<String, Error> {
+        unsafe {
+            let list = sys::device_consistency_signature_list_alloc();
+            assert!(!list.is_null());
+
+            for signature in signatures {
+                let result = sys::device_consistency_signature_list_push_back(
+                    list,
+                    signature.raw.as_ptr(),
+                );
+                if let Err(e) = result.into_result() {
+                    sys::device_consistency_signature_list_free(list);
+                    return Err(e.into());
+                }
+            }
+
+            let mut code: *mut c_char = ptr::null_mut();
+            let result = sys::device_consistency_code_generate_for(
+                self.raw.as_ptr(),
+                list,
+                &mut code,
+                ctx.raw(),
+            );
+            sys::device_consistency_signature_list_free(list);
+            result.into_result()?;
+
+            assert!(!code.is_null());
+            let s = CStr::from_ptr(code).to_string_lossy().into_owned();
+            free(code as *mut c_void);
+
+            Ok(s)
+        }
+    }
+}
+
+impl Debug for DeviceConsistencyCommitment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeviceConsistencyCommitment")
+            .field("generation", &self.generation())
+            .finish()
+    }
+}
+
+/// One device's signature over a [`DeviceConsistencyCommitment`], sent to
+/// the user's other devices.
+#[derive(Clone)]
+pub struct DeviceConsistencyMessage {
+    raw: Raw<sys::device_consistency_message>,
+}
+
+impl DeviceConsistencyMessage {
+    /// Sign a commitment with this device's identity key.
+    pub fn new(
+        ctx: &Context,
+        commitment: &DeviceConsistencyCommitment,
+        identity_key_pair: &IdentityKeyPair,
+    ) -> Result<DeviceConsistencyMessage, Error> {
+        let key_pair = KeyPair::new(
+            &identity_key_pair.public_key()?,
+            &identity_key_pair.private_key()?,
+        )?;
+
+        unsafe {
+            let mut raw = ptr::null_mut();
+            sys::device_consistency_message_create_from_pair(
+                &mut raw,
+                commitment.raw.as_ptr(),
+                key_pair.raw.as_ptr(),
+                ctx.raw(),
+            )
+            .into_result()?;
+
+            Ok(DeviceConsistencyMessage {
+                raw: Raw::from_ptr(raw),
+            })
+        }
+    }
+
+    /// Parse a message received from another device, checking it was signed
+    /// over our commitment by that device's identity key.
+    pub fn deserialize(
+        ctx: &Context,
+        commitment: &DeviceConsistencyCommitment,
+        data: &[u8],
+        identity_key: &PublicKey,
+    ) -> Result<DeviceConsistencyMessage, Error> {
+        unsafe {
+            let mut raw = ptr::null_mut();
+            sys::device_consistency_message_create_from_serialized(
+                &mut raw,
+                commitment.raw.as_ptr(),
+                data.as_ptr(),
+                data.len(),
+                identity_key.raw.as_ptr(),
+                ctx.raw(),
+            )
+            .into_result()?;
+
+            Ok(DeviceConsistencyMessage {
+                raw: Raw::from_ptr(raw),
+            })
+        }
+    }
+
+    /// The bytes to send to the user's other devices.
+    pub fn serialize(&self) -> &[u8] {
+        unsafe {
+            buffer_contents(sys::device_consistency_message_get_serialized(
+                self.raw.as_const_ptr(),
+            ))
+        }
+    }
+
+    pub fn signature(&self) -> DeviceConsistencySignature {
+        unsafe {
+            let raw = sys::device_consistency_message_get_signature(
+                self.raw.as_const_ptr(),
+            );
+
+            DeviceConsistencySignature {
+                raw: Raw::copied_from(raw),
+            }
+        }
+    }
+
+    pub fn generation(&self) -> u32 {
+        unsafe {
+            sys::device_consistency_message_get_generation(
+                self.raw.as_const_ptr(),
+            )
+        }
+    }
+}
+
+impl Debug for DeviceConsistencyMessage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeviceConsistencyMessage")
+            .field("generation", &self.generation())
+            .finish()
+    }
+}
+
+/// The signature from a [`DeviceConsistencyMessage`].
+#[derive(Clone)]
+pub struct DeviceConsistencySignature {
+    raw: Raw<sys::device_consistency_signature>,
+}
+
+impl DeviceConsistencySignature {
+    pub fn signature(&self) -> &[u8] {
+        unsafe {
+            buffer_contents(sys::device_consistency_signature_get_signature(
+                self.raw.as_const_ptr(),
+            ))
+        }
+    }
+
+    pub fn vrf_output(&self) -> &[u8] {
+        unsafe {
+            buffer_contents(sys::device_consistency_signature_get_vrf_output(
+                self.raw.as_const_ptr(),
+            ))
+        }
+    }
+}
+
+impl Debug for DeviceConsistencySignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeviceConsistencySignature").finish()
+    }
+}
+
+/// Borrow the contents of a buffer owned by one of the device consistency
+/// objects.
+unsafe fn buffer_contents<'a>(buffer: *const sys::signal_buffer) -> &'a [u8] {
+    assert!(!buffer.is_null());
+
+    std::slice::from_raw_parts(
+        sys::signal_buffer_const_data(buffer),
+        sys::signal_buffer_len(buffer),
+    )
+}
+
+#[cfg(all(test, feature = "crypto-openssl"))]
+mod tests {
+    use super::*;
+    use crate::crypto::OpenSSLCrypto;
+
+    #[test]
+    fn all_devices_generate_the_same_code() {
+        let ctx = Context::new(OpenSSLCrypto).unwrap();
+        let devices: Vec<_> = (0..3)
+            .map(|_| ctx.generate_identity_key_pair().unwrap())
+            .collect();
+        let keys: Vec<_> =
+            devices.iter().map(|d| d.public_key().unwrap()).collect();
+        let commitment =
+            DeviceConsistencyCommitment::new(&ctx, 1, &keys).unwrap();
+
+        let messages: Vec<_> = devices
+            .iter()
+            .map(|d| DeviceConsistencyMessage::new(&ctx, &commitment, d))
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        // the first device receives the other devices' messages
+        let mut signatures = vec![messages[0].signature()];
+        for (message, key) in messages.iter().zip(&keys).skip(1) {
+            let received = DeviceConsistencyMessage::deserialize(
+                &ctx,
+                &commitment,
+                message.serialize(),
+                key,
+            )
+            .unwrap();
+            signatures.push(received.signature());
+        }
+
+        // and another device, with its keys in a different order
+        let reversed: Vec<_> = keys.iter().rev().cloned().collect();
+        let other =
+            DeviceConsistencyCommitment::new(&ctx, 1, &reversed).unwrap();
+        let other_signatures: Vec<_> =
+            messages.iter().rev().map(|m| m.signature()).collect();
+
+        let code = commitment.code(&ctx, &signatures).unwrap();
+        assert_eq!(code.len(), 6);
+        assert_eq!(code, other.code(&ctx, &other_signatures).unwrap());
+    }
+}
@@ -0,0 +1,368 @@
+use crate::{
+    errors::{FromInternalErrorCode, InternalError},
+    keys::PublicKey,
+    raw_ptr::Raw,
+    Buffer, Context,
+};
+use failure::Error;
+use std::{
+    convert::TryFrom,
+    ffi::{CStr, CString},
+    fmt::{self, Debug, Display, Formatter},
+    os::raw::{c_char, c_int},
+    ptr,
+};
+
+/// The version of scannable fingerprint to generate.
+const SCANNABLE_FINGERPRINT_VERSION: c_int = 1;
+
+/// The "safety number" two users compare to check they are talking to each
+/// other, derived from both users' identity keys.
+#[derive(Clone)]
+pub struct Fingerprint {
+    raw: Raw<sys::fingerprint>,
+}
+
+impl Fingerprint {
+    /// Generate the fingerprint for a conversation.
+    ///
+    /// The stable identifiers are normally each user's phone number, and
+    /// Signal uses 5200 iterations.
+    pub fn generate(
+        ctx: &Context,
+        local_id: &str,
+        local_identity: &PublicKey,
+        remote_id: &str,
+        remote_identity: &PublicKey,
+        iterations: u32,
+    ) -> Result<Fingerprint, Error> {
+        let local_id = CString::new(local_id)?;
+        let remote_id = CString::new(remote_id)?;
+        let iterations = c_int::try_from(iterations)?;
+
+        unsafe {
+            let mut generator = ptr::null_mut();
+            sys::fingerprint_generator_create(
+                &mut generator,
+                iterations,
+                SCANNABLE_FINGERPRINT_VERSION,
+                ctx.raw(),
+            )
+            .into_result()?;
+
+            let mut raw = ptr::null_mut();
+            let result = sys::fingerprint_generator_create_for(
+                generator,
+                local_id.as_ptr(),
+                local_identity.raw.as_const_ptr(),
+                remote_id.as_ptr(),
+                remote_identity.raw.as_const_ptr(),
+                &mut raw,
+            );
+            sys::fingerprint_generator_free(generator);
+            result.into_result()?;
+
+            Ok(Fingerprint {
+                raw: Raw::from_ptr(raw),
+            })
+        }
+    }
+
+    /// The version of the fingerprint which is shown as a QR code, for the
+    /// other user to scan.
+    pub fn scannable(&self) -> ScannableFingerprint {
+        unsafe {
+            let raw = sys::fingerprint_get_scannable(self.raw.as_const_ptr());
+
+            ScannableFingerprint {
+                raw: Raw::copied_from(raw),
+            }
+        }
+    }
+
+    /// The numeric version of the fingerprint, meant to be read out loud.
+    pub fn displayable(&self) -> DisplayableFingerprint {
+        unsafe {
+            let raw = sys::fingerprint_get_displayable(self.raw.as_const_ptr());
+
+            DisplayableFingerprint {
+                raw: Raw::copied_from(raw),
+            }
+        }
+    }
+}
+
+impl Debug for Fingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fingerprint")
+            .field("displayable", &self.displayable())
+            .field("scannable", &self.scannable())
+            .finish()
+    }
+}
+
+/// A fingerprint as a 60 digit safety number.
+#[derive(Clone)]
+pub struct DisplayableFingerprint {
+    raw: Raw<sys::displayable_fingerprint>,
+}
+
+impl DisplayableFingerprint {
+    /// The half of the digits derived from our own identity.
+    pub fn local(&self) -> &str {
+        unsafe {
+            digits(sys::displayable_fingerprint_local(self.raw.as_const_ptr()))
+        }
+    }
+
+    /// The half of the digits derived from the other user's identity.
+    pub fn remote(&self) -> &str {
+        unsafe {
+            digits(sys::displayable_fingerprint_remote(self.raw.as_const_ptr()))
+        }
+    }
+
+    /// The full safety number, which both users should see the same digits
+    /// for.
+    pub fn text(&self) -> &str {
+        unsafe {
+            digits(sys::displayable_fingerprint_text(self.raw.as_const_ptr()))
+        }
+    }
+}
+
+impl Display for DisplayableFingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(self.text(), f)
+    }
+}
+
+impl Debug for DisplayableFingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("DisplayableFingerprint")
+            .field(&self.text())
+            .finish()
+    }
+}
+
+/// A fingerprint in the form encoded in a QR code.
+///
+/// One user scans the other's QR code, deserializes it, and uses
+/// [`ScannableFingerprint::compare()`] to check it against their own.
+#[derive(Clone)]
+pub struct ScannableFingerprint {
+    raw: Raw<sys::scannable_fingerprint>,
+}
+
+impl ScannableFingerprint {
+    pub fn deserialize(
+        ctx: &Context,
+        data: &[u8],
+    ) -> Result<ScannableFingerprint, Error> {
+        unsafe {
+            ScannableFingerprint::deserialize_with_raw_context(ctx.raw(), data)
+        }
+    }
+
+    unsafe fn deserialize_with_raw_context(
+        ctx: *mut sys::signal_context,
+        data: &[u8],
+    ) -> Result<ScannableFingerprint, Error> {
+        let mut raw = ptr::null_mut();
+        sys::scannable_fingerprint_deserialize(
+            &mut raw,
+            data.as_ptr(),
+            data.len(),
+            ctx,
+        )
+        .into_result()?;
+
+        Ok(ScannableFingerprint {
+            raw: Raw::from_ptr(raw),
+        })
+    }
+
+    /// The QR code's payload.
+    pub fn serialize(&self) -> Result<Buffer, Error> {
+        unsafe {
+            let mut buffer = ptr::null_mut();
+            sys::scannable_fingerprint_serialize(
+                &mut buffer,
+                self.raw.as_const_ptr(),
+            )
+            .into_result()?;
+
+            Ok(Buffer::from_raw(buffer))
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        unsafe {
+            sys::scannable_fingerprint_get_version(self.raw.as_const_ptr())
+        }
+    }
+
+    /// Check a fingerprint scanned from the other user's device against our
+    /// own, returning `true` if both users have the same identity keys.
+    ///
+    /// A fingerprint with the same version but different identity keys
+    /// (e.g. a man-in-the-middle, or a conversation with somebody else
+    /// entirely) gives `Ok(false)`. A fingerprint made with a different
+    /// version fails with [`InternalError::FPVersionMismatch`].
+    ///
+    /// Only version 0 fingerprints include the users' stable identifiers, so
+    /// [`InternalError::FPIdentMismatch`] is only returned when comparing
+    /// two version 0 fingerprints whose identifiers don't match.
+    pub fn compare(
+        &self,
+        scanned: &ScannableFingerprint,
+    ) -> Result<bool, Error> {
+        unsafe {
+            let result = sys::scannable_fingerprint_compare(
+                self.raw.as_const_ptr(),
+                scanned.raw.as_const_ptr(),
+            );
+
+            match result {
+                0 => Ok(false),
+                1 => Ok(true),
+                other => Err(InternalError::from_error_code(other)
+                    .unwrap_or(InternalError::Other(other))
+                    .into()),
+            }
+        }
+    }
+}
+
+impl_try_from_bytes!(
+    ScannableFingerprint,
+    ScannableFingerprint::deserialize_with_raw_context
+);
+
+impl Debug for ScannableFingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScannableFingerprint")
+            .field("version", &self.version())
+            .finish()
+    }
+}
+
+/// Borrow a string of digits owned by a `displayable_fingerprint`.
+unsafe fn digits<'a>(raw: *const c_char) -> &'a str {
+    assert!(!raw.is_null());
+
+    CStr::from_ptr(raw)
+        .to_str()
+        .expect("Displayable fingerprints only contain digits")
+}
+
+#[cfg(all(test, feature = "crypto-openssl"))]
+mod tests {
+    use super::*;
+    use crate::crypto::OpenSSLCrypto;
+
+    #[test]
+    fn both_users_see_the_same_safety_number() {
+        let ctx = Context::new(OpenSSLCrypto).unwrap();
+        let alice = ctx.generate_identity_key_pair().unwrap();
+        let bob = ctx.generate_identity_key_pair().unwrap();
+        let alice_key = alice.public_key().unwrap();
+        let bob_key = bob.public_key().unwrap();
+
+        let alices = Fingerprint::generate(
+            &ctx,
+            "+14152222222",
+            &alice_key,
+            "+14153333333",
+            &bob_key,
+            1024,
+        )
+        .unwrap()
+        .displayable();
+        let bobs = Fingerprint::generate(
+            &ctx,
+            "+14153333333",
+            &bob_key,
+            "+14152222222",
+            &alice_key,
+            1024,
+        )
+        .unwrap()
+        .displayable();
+
+        assert_eq!(alices.text().len(), 60);
+        assert!(alices.text().bytes().all(|b| b.is_ascii_digit()));
+        assert_eq!(alices.text(), bobs.text());
+        assert_eq!(alices.local(), bobs.remote());
+    }
+
+    #[test]
+    fn scan_the_other_users_fingerprint() {
+        let ctx = Context::new(OpenSSLCrypto).unwrap();
+        let alice = ctx.generate_identity_key_pair().unwrap();
+        let bob = ctx.generate_identity_key_pair().unwrap();
+        let mallory = ctx.generate_identity_key_pair().unwrap();
+        let alice_key = alice.public_key().unwrap();
+        let bob_key = bob.public_key().unwrap();
+        let mallory_key = mallory.public_key().unwrap();
+        let fingerprint = |local, local_key, remote, remote_key| {
+            Fingerprint::generate(
+                &ctx, local, local_key, remote, remote_key, 1024,
+            )
+            .unwrap()
+            .scannable()
+        };
+
+        let alices =
+            fingerprint("+14152222222", &alice_key, "+14153333333", &bob_key);
+        let bobs =
+            fingerprint("+14153333333", &bob_key, "+14152222222", &alice_key);
+        let mitm = fingerprint(
+            "+14153333333",
+            &mallory_key,
+            "+14152222222",
+            &alice_key,
+        );
+
+        let qr_code = bobs.serialize().unwrap();
+        let scanned =
+            ScannableFingerprint::deserialize(&ctx, qr_code.as_slice())
+                .unwrap();
+        assert!(alices.compare(&scanned).unwrap());
+        assert!(!alices.compare(&mitm).unwrap());
+    }
+
+    #[test]
+    fn fingerprints_from_other_versions_are_rejected() {
+        let ctx = Context::new(OpenSSLCrypto).unwrap();
+        let alice = ctx.generate_identity_key_pair().unwrap();
+        let bob = ctx.generate_identity_key_pair().unwrap();
+        let alice_key = alice.public_key().unwrap();
+        let bob_key = bob.public_key().unwrap();
+        let fingerprint = |local, local_key, remote, remote_key| {
+            Fingerprint::generate(
+                &ctx, local, local_key, remote, remote_key, 1024,
+            )
+            .unwrap()
+            .scannable()
+        };
+        let alices =
+            fingerprint("+14152222222", &alice_key, "+14153333333", &bob_key);
+        let bobs =
+            fingerprint("+14153333333", &bob_key, "+14152222222", &alice_key);
+
+        // the version is the first field in the QR code's protobuf
+        let mut qr_code = bobs.serialize().unwrap().as_slice().to_vec();
+        assert_eq!(&qr_code[..2], &[0x08, SCANNABLE_FINGERPRINT_VERSION as u8]);
+        qr_code[1] = 0;
+        let scanned =
+            ScannableFingerprint::deserialize(&ctx, &qr_code).unwrap();
+        assert_eq!(scanned.version(), 0);
+
+        let err = alices.compare(&scanned).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<InternalError>(),
+            Some(&InternalError::FPVersionMismatch)
+        );
+    }
+}
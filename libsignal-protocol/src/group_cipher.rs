@@ -0,0 +1,150 @@
+use crate::{
+    context::{Context, ContextInner},
+    errors::FromInternalErrorCode,
+    messages::SenderKeyMessage,
+    raw_ptr::Raw,
+    sender_key_name::SenderKeyName,
+    session_cipher::DecryptionCallback,
+    store_context::{StoreContext, StoreContextInner},
+    Buffer,
+};
+use failure::Error;
+use std::{
+    fmt::{self, Debug, Formatter},
+    os::raw::{c_int, c_void},
+    ptr,
+    rc::Rc,
+};
+
+/// Encrypts and decrypts group messages using a sender's key.
+///
+/// The cipher is bound to a single [`SenderKeyName`]: our own address when
+/// encrypting, or the sender's when decrypting.
+///
+/// The sender key session must have been set up with a
+/// [`GroupSessionBuilder`](crate::GroupSessionBuilder) first.
+///
+/// This is a cheap handle around shared state, so cloning it gives another
+/// reference to the same underlying cipher.
+#[derive(Clone)]
+pub struct GroupCipher {
+    inner: Rc<GroupCipherInner>,
+}
+
+struct GroupCipherInner {
+    raw: *mut sys::group_cipher,
+    // `group_cipher` keeps a pointer to the sender key name it was created
+    // with
+    _name: SenderKeyName,
+    // both these fields must outlive `group_cipher`
+    _store_ctx: Rc<StoreContextInner>,
+    _ctx: Rc<ContextInner>,
+}
+
+impl GroupCipher {
+    pub fn new(
+        ctx: &Context,
+        store_context: &StoreContext,
+        name: &SenderKeyName,
+    ) -> Result<GroupCipher, Error> {
+        let name = name.clone();
+
+        unsafe {
+            let mut raw = ptr::null_mut();
+            sys::group_cipher_create(
+                &mut raw,
+                store_context.raw(),
+                name.raw(),
+                ctx.raw(),
+            )
+            .into_result()?;
+            sys::group_cipher_set_decryption_callback(
+                raw,
+                Some(decryption_callback),
+            );
+
+            Ok(GroupCipher {
+                inner: Rc::new(GroupCipherInner {
+                    raw,
+                    _name: name,
+                    _store_ctx: Rc::clone(&store_context.0),
+                    _ctx: Rc::clone(&ctx.0),
+                }),
+            })
+        }
+    }
+
+    /// Encrypt a message to the group with our own sender key.
+    pub fn encrypt(&self, message: &[u8]) -> Result<SenderKeyMessage, Error> {
+        unsafe {
+            let mut raw = ptr::null_mut();
+            sys::group_cipher_encrypt(
+                self.inner.raw,
+                message.as_ptr(),
+                message.len(),
+                &mut raw,
+            )
+            .into_result()?;
+
+            // group_cipher_encrypt() always creates a sender_key_message
+            Ok(SenderKeyMessage {
+                raw: Raw::from_ptr(raw as *mut sys::sender_key_message),
+            })
+        }
+    }
+
+    /// Decrypt a message sent to the group, saving the updated sender key.
+    pub fn decrypt(&self, message: &SenderKeyMessage) -> Result<Buffer, Error> {
+        self.decrypt_with_callback(message, |_| Ok(()))
+    }
+
+    /// Decrypt a [`SenderKeyMessage`], calling `callback` with the
+    /// plaintext before the updated sender key is saved.
+    ///
+    /// If the callback fails, the sender key isn't saved.
+    pub fn decrypt_with_callback<F>(
+        &self,
+        message: &SenderKeyMessage,
+        callback: F,
+    ) -> Result<Buffer, Error>
+    where
+        F: FnOnce(&[u8]) -> Result<(), Error>,
+    {
+        let mut state = DecryptionCallback::new(callback);
+
+        unsafe {
+            let mut plaintext = ptr::null_mut();
+            let result = sys::group_cipher_decrypt(
+                self.inner.raw,
+                message.raw.as_ptr(),
+                state.as_decrypt_context(),
+                &mut plaintext,
+            );
+            state.check(result)?;
+
+            Ok(Buffer::from_raw(plaintext))
+        }
+    }
+}
+
+unsafe extern "C" fn decryption_callback(
+    _cipher: *mut sys::group_cipher,
+    plaintext: *mut sys::signal_buffer,
+    decrypt_context: *mut c_void,
+) -> c_int {
+    DecryptionCallback::invoke(decrypt_context, plaintext)
+}
+
+impl Debug for GroupCipher {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GroupCipher").finish()
+    }
+}
+
+impl Drop for GroupCipherInner {
+    fn drop(&mut self) {
+        unsafe {
+            sys::group_cipher_free(self.raw);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{
+    context::{Context, ContextInner},
+    errors::FromInternalErrorCode,
+    messages::SenderKeyDistributionMessage,
+    raw_ptr::Raw,
+    sender_key_name::SenderKeyName,
+    store_context::{StoreContext, StoreContextInner},
+};
+use failure::Error;
+use std::{
+    fmt::{self, Debug, Formatter},
+    ptr,
+    rc::Rc,
+};
+
+/// Sets up sender key sessions for group messaging.
+///
+/// Each member of a group creates a [`SenderKeyDistributionMessage`] with
+/// [`GroupSessionBuilder::create_session()`] and sends it to every other
+/// member over their one-to-one sessions. The recipients pass it to
+/// [`GroupSessionBuilder::process_session()`], after which they can decrypt
+/// that member's group messages.
+pub struct GroupSessionBuilder {
+    raw: *mut sys::group_session_builder,
+    // both these fields must outlive `group_session_builder`
+    _store_ctx: Rc<StoreContextInner>,
+    _ctx: Rc<ContextInner>,
+}
+
+impl GroupSessionBuilder {
+    pub fn new(
+        ctx: &Context,
+        store_context: &StoreContext,
+    ) -> Result<GroupSessionBuilder, Error> {
+        unsafe {
+            let mut raw = ptr::null_mut();
+            sys::group_session_builder_create(
+                &mut raw,
+                store_context.raw(),
+                ctx.raw(),
+            )
+            .into_result()?;
+
+            Ok(GroupSessionBuilder {
+                raw,
+                _store_ctx: Rc::clone(&store_context.0),
+                _ctx: Rc::clone(&ctx.0),
+            })
+        }
+    }
+
+    /// Save the sender key another group member sent us, so their messages
+    /// to the group can be decrypted.
+    ///
+    /// The [`SenderKeyName`] is the group and the member who sent the
+    /// [`SenderKeyDistributionMessage`].
+    pub fn process_session(
+        &self,
+        name: &SenderKeyName,
+        distribution_message: &SenderKeyDistributionMessage,
+    ) -> Result<(), Error> {
+        unsafe {
+            sys::group_session_builder_process_session(
+                self.raw,
+                name.raw(),
+                distribution_message.raw.as_ptr(),
+            )
+            .into_result()?;
+        }
+
+        Ok(())
+    }
+
+    /// Create (or reuse) our own sender key for a group, returning the
+    /// [`SenderKeyDistributionMessage`] to send to the other members.
+    ///
+    /// The [`SenderKeyName`] is the group and our own address.
+    pub fn create_session(
+        &self,
+        name: &SenderKeyName,
+    ) -> Result<SenderKeyDistributionMessage, Error> {
+        unsafe {
+            let mut raw = ptr::null_mut();
+            sys::group_session_builder_create_session(
+                self.raw,
+                &mut raw,
+                name.raw(),
+            )
+            .into_result()?;
+
+            Ok(SenderKeyDistributionMessage {
+                raw: Raw::from_ptr(raw),
+            })
+        }
+    }
+}
+
+impl Debug for GroupSessionBuilder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GroupSessionBuilder").finish()
+    }
+}
+
+impl Drop for GroupSessionBuilder {
+    fn drop(&mut self) {
+        unsafe {
+            sys::group_session_builder_free(self.raw);
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use crate::{
+    attachment::AttachmentKeys,
+    errors::InternalError,
+    keys::{IdentityKeyPair, SessionSignedPreKey},
+    scrub::Scrubbed,
+    Context,
+};
+use failure::Error;
+use std::convert::TryInto;
+
+const FORMAT_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const HEADER_LEN: usize = 1 + 4 + 4 + SALT_LEN;
+
+/// Argon2id memory cost in KiB (64 MiB).
+const MEM_COST: u32 = 64 * 1024;
+/// Argon2id iterations.
+const TIME_COST: u32 = 3;
+/// The KDF parameters come from the blob's header, which is read before
+/// anything is authenticated, so only allow a little headroom over the
+/// defaults to stop a crafted export from exhausting memory or CPU.
+const MAX_MEM_COST: u32 = 4 * MEM_COST;
+const MAX_TIME_COST: u32 = 10;
+
+/// Everything needed to move an identity to a new device.
+#[derive(Debug)]
+pub struct IdentityExport {
+    pub identity_key_pair: IdentityKeyPair,
+    pub registration_id: u32,
+    /// Signed pre-keys to carry across so peers holding older bundles can
+    /// still establish sessions. This may be left empty.
+    pub signed_pre_keys: Vec<SessionSignedPreKey>,
+}
+
+impl Context {
+    /// Encrypt an identity with a passphrase so it can be transferred to
+    /// another device.
+    ///
+    /// The passphrase is stretched with Argon2id, and the blob is encrypted
+    /// with AES-256-CBC and authenticated with HMAC-SHA256 using the
+    /// [`Crypto`] provider this [`Context`] was created with. The KDF
+    /// parameters are recorded in the blob, so they can be raised in the
+    /// future without breaking old exports.
+    ///
+    /// [`Crypto`]: crate::crypto::Crypto
+    pub fn export_identity(
+        &self,
+        export: &IdentityExport,
+        passphrase: &str,
+    ) -> Result<Vec<u8>, Error> {
+        let identity_key_pair = Scrubbed(export.identity_key_pair.serialize()?);
+        let signed_pre_keys = export
+            .signed_pre_keys
+            .iter()
+            .map(|signed_pre_key| signed_pre_key.serialize().map(Scrubbed))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // allocate everything up front so growing the vector doesn't leave
+        // copies of the private key behind
+        let len = 4
+            + 4
+            + identity_key_pair.0.len()
+            + 4
+            + signed_pre_keys.iter().map(|s| 4 + s.0.len()).sum::<usize>();
+        let mut plaintext = Scrubbed(Vec::with_capacity(len));
+        plaintext
+            .0
+            .extend_from_slice(&export.registration_id.to_be_bytes());
+        write_chunk(&mut plaintext.0, identity_key_pair.0.as_slice());
+        plaintext
+            .0
+            .extend_from_slice(&(signed_pre_keys.len() as u32).to_be_bytes());
+        for signed_pre_key in &signed_pre_keys {
+            write_chunk(&mut plaintext.0, signed_pre_key.0.as_slice());
+        }
+
+        let mut salt = [0; SALT_LEN];
+        self.crypto().fill_random(&mut salt)?;
+
+        let mut blob = Vec::with_capacity(HEADER_LEN);
+        blob.push(FORMAT_VERSION);
+        blob.extend_from_slice(&MEM_COST.to_be_bytes());
+        blob.extend_from_slice(&TIME_COST.to_be_bytes());
+        blob.extend_from_slice(&salt);
+
+        let keys = derive_keys(passphrase, &salt, MEM_COST, TIME_COST)?;
+        blob.extend(keys.encrypt(self, &plaintext.0)?);
+
+        Ok(blob)
+    }
+
+    /// Decrypt an identity previously encrypted with
+    /// [`Context::export_identity()`].
+    ///
+    /// A wrong passphrase or a tampered blob both fail with
+    /// [`InternalError::InvalidMAC`].
+    pub fn import_identity(
+        &self,
+        blob: &[u8],
+        passphrase: &str,
+    ) -> Result<IdentityExport, Error> {
+        if blob.len() < HEADER_LEN {
+            return Err(InternalError::InvalidMessage.into());
+        }
+        if blob[0] != FORMAT_VERSION {
+            return Err(InternalError::InvalidVersion.into());
+        }
+
+        let mem_cost = read_u32(&blob[1..])?;
+        let time_cost = read_u32(&blob[5..])?;
+        let salt = &blob[9..HEADER_LEN];
+
+        if mem_cost > MAX_MEM_COST || time_cost > MAX_TIME_COST {
+            return Err(InternalError::InvalidArgument.into());
+        }
+
+        let keys = derive_keys(passphrase, salt, mem_cost, time_cost)?;
+        let plaintext = Scrubbed(keys.decrypt(self, &blob[HEADER_LEN..])?);
+
+        let mut rest = &plaintext.0[..];
+        let registration_id = read_u32(rest)?;
+        rest = &rest[4..];
+        let identity_key_pair =
+            IdentityKeyPair::deserialize(self, read_chunk(&mut rest)?)?;
+        let count = read_u32(rest)?;
+        rest = &rest[4..];
+
+        let mut signed_pre_keys = Vec::new();
+        for _ in 0..count {
+            let serialized = read_chunk(&mut rest)?;
+            signed_pre_keys
+                .push(SessionSignedPreKey::deserialize(self, serialized)?);
+        }
+
+        Ok(IdentityExport {
+            identity_key_pair,
+            registration_id,
+            signed_pre_keys,
+        })
+    }
+}
+
+fn derive_keys(
+    passphrase: &str,
+    salt: &[u8],
+    mem_cost: u32,
+    time_cost: u32,
+) -> Result<AttachmentKeys, Error> {
+    let config = argon2::Config {
+        variant: argon2::Variant::Argon2id,
+        version: argon2::Version::Version13,
+        mem_cost,
+        time_cost,
+        lanes: 1,
+        thread_mode: argon2::ThreadMode::Sequential,
+        secret: &[],
+        ad: &[],
+        hash_length: AttachmentKeys::LEN as u32,
+    };
+
+    let key_material =
+        Scrubbed(argon2::hash_raw(passphrase.as_bytes(), salt, &config)?);
+
+    AttachmentKeys::from_bytes(&key_material.0)
+}
+
+fn write_chunk(buffer: &mut Vec<u8>, chunk: &[u8]) {
+    buffer.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+    buffer.extend_from_slice(chunk);
+}
+
+fn read_u32(bytes: &[u8]) -> Result<u32, Error> {
+    match bytes.get(..4) {
+        Some(bytes) => Ok(u32::from_be_bytes(bytes.try_into()?)),
+        None => Err(InternalError::InvalidMessage.into()),
+    }
+}
+
+fn read_chunk<'a>(bytes: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+    let len = read_u32(bytes)? as usize;
+    let rest = &bytes[4..];
+
+    if rest.len() < len {
+        return Err(InternalError::InvalidMessage.into());
+    }
+
+    let (chunk, rest) = rest.split_at(len);
+    *bytes = rest;
+    Ok(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "crypto-openssl")]
+    use crate::{crypto::OpenSSLCrypto, ids::SignedPreKeyId, Timestamp};
+
+    #[test]
+    fn chunks_are_length_prefixed() {
+        let mut buffer = Vec::new();
+        write_chunk(&mut buffer, b"first");
+        write_chunk(&mut buffer, b"");
+        write_chunk(&mut buffer, b"third");
+
+        let mut rest = &buffer[..];
+        assert_eq!(read_chunk(&mut rest).unwrap(), b"first");
+        assert_eq!(read_chunk(&mut rest).unwrap(), b"");
+        assert_eq!(read_chunk(&mut rest).unwrap(), b"third");
+        assert!(rest.is_empty());
+        assert!(read_chunk(&mut &buffer[..6]).is_err());
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        let ctx = Context::default();
+        let mut blob = vec![0; HEADER_LEN + 64];
+        blob[0] = FORMAT_VERSION + 1;
+
+        let err = ctx.import_identity(&blob, "hunter2").err().unwrap();
+
+        assert_eq!(
+            err.downcast_ref::<InternalError>(),
+            Some(&InternalError::InvalidVersion)
+        );
+    }
+
+    #[test]
+    fn kdf_parameters_are_capped() {
+        let ctx = Context::default();
+        let mut blob = vec![0; HEADER_LEN + 64];
+        blob[0] = FORMAT_VERSION;
+        blob[1..5].copy_from_slice(&(MAX_MEM_COST + 1).to_be_bytes());
+        blob[5..9].copy_from_slice(&TIME_COST.to_be_bytes());
+
+        let err = ctx.import_identity(&blob, "hunter2").err().unwrap();
+
+        assert_eq!(
+            err.downcast_ref::<InternalError>(),
+            Some(&InternalError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "crypto-openssl")]
+    fn export_and_import_an_identity() {
+        let ctx = Context::new(OpenSSLCrypto).unwrap();
+        let identity_key_pair = ctx.generate_identity_key_pair().unwrap();
+        let signed_pre_key = ctx
+            .generate_signed_pre_key(
+                &identity_key_pair,
+                SignedPreKeyId::new(5),
+                Timestamp::now(),
+            )
+            .unwrap();
+        let export = IdentityExport {
+            identity_key_pair,
+            registration_id: 1234,
+            signed_pre_keys: vec![signed_pre_key],
+        };
+
+        let blob = ctx.export_identity(&export, "hunter2").unwrap();
+        assert!(ctx.import_identity(&blob, "hunter3").is_err());
+        let got = ctx.import_identity(&blob, "hunter2").unwrap();
+
+        assert_eq!(got.registration_id, 1234);
+        assert_eq!(
+            got.identity_key_pair.serialize().unwrap().as_slice(),
+            export.identity_key_pair.serialize().unwrap().as_slice()
+        );
+        assert_eq!(got.signed_pre_keys.len(), 1);
+        assert_eq!(got.signed_pre_keys[0].id(), SignedPreKeyId::new(5));
+    }
+}
@@ -1,6 +1,37 @@
-use std::os::raw::{c_int, c_void};
+use crate::{errors::InternalError, keys::IdentityKeyPair, Address};
+use std::{
+    os::raw::{c_int, c_void},
+    slice,
+};
 
-pub trait IdentityKeyStore {}
+/// Where the local identity, and the identities of the people we talk to,
+/// are kept.
+pub trait IdentityKeyStore {
+    /// Get the local client's identity key pair.
+    fn get_identity_key_pair(&self) -> Result<IdentityKeyPair, InternalError>;
+
+    /// Get the local client's registration ID.
+    fn get_local_registration_id(&self) -> Result<u32, InternalError>;
+
+    /// Remember the serialized identity key a remote client is using. An
+    /// empty `identity_key` means the identity should be forgotten.
+    fn save_identity(
+        &self,
+        address: &Address,
+        identity_key: &[u8],
+    ) -> Result<(), InternalError>;
+
+    /// Should we trust this serialized identity key for the remote client?
+    ///
+    /// Most applications trust an identity the first time they see it and
+    /// only reject it if it changes without the user confirming the new
+    /// key. Sessions can't be set up with an untrusted identity.
+    fn is_trusted_identity(
+        &self,
+        address: &Address,
+        identity_key: &[u8],
+    ) -> Result<bool, InternalError>;
+}
 
 pub(crate) fn new_vtable<I: IdentityKeyStore + 'static>(
     identity_key_store: I,
@@ -20,36 +51,101 @@ pub(crate) fn new_vtable<I: IdentityKeyStore + 'static>(
 struct State(Box<dyn IdentityKeyStore>);
 
 unsafe extern "C" fn get_identity_key_pair(
-    _public_data: *mut *mut sys::signal_buffer,
-    _private_data: *mut *mut sys::signal_buffer,
-    _user_data: *mut c_void,
+    public_data: *mut *mut sys::signal_buffer,
+    private_data: *mut *mut sys::signal_buffer,
+    user_data: *mut c_void,
 ) -> c_int {
-    unimplemented!()
+    assert!(!user_data.is_null());
+    assert!(!public_data.is_null());
+    assert!(!private_data.is_null());
+    let user_data = &*(user_data as *const State);
+
+    let key_pair = match user_data.0.get_identity_key_pair() {
+        Ok(key_pair) => key_pair,
+        Err(e) => return e.code(),
+    };
+    let raw = key_pair.raw.as_const_ptr();
+
+    let ret = sys::ec_public_key_serialize(
+        public_data,
+        sys::ratchet_identity_key_pair_get_public(raw),
+    );
+    if ret < 0 {
+        return ret;
+    }
+
+    let ret = sys::ec_private_key_serialize(
+        private_data,
+        sys::ratchet_identity_key_pair_get_private(raw),
+    );
+    if ret < 0 {
+        sys::signal_buffer_free(*public_data);
+        *public_data = std::ptr::null_mut();
+        return ret;
+    }
+
+    sys::SG_SUCCESS as c_int
 }
 
 unsafe extern "C" fn get_local_registration_id(
-    _user_data: *mut c_void,
-    _registration_id: *mut u32,
+    user_data: *mut c_void,
+    registration_id: *mut u32,
 ) -> c_int {
-    unimplemented!()
+    assert!(!user_data.is_null());
+    assert!(!registration_id.is_null());
+    let user_data = &*(user_data as *const State);
+
+    match user_data.0.get_local_registration_id() {
+        Ok(id) => {
+            *registration_id = id;
+            sys::SG_SUCCESS as c_int
+        },
+        Err(e) => e.code(),
+    }
 }
 
 unsafe extern "C" fn save_identity(
-    _address: *const sys::signal_protocol_address,
-    _key_data: *mut u8,
-    _key_len: usize,
-    _user_data: *mut c_void,
+    address: *const sys::signal_protocol_address,
+    key_data: *mut u8,
+    key_len: usize,
+    user_data: *mut c_void,
 ) -> c_int {
-    unimplemented!()
+    assert!(!user_data.is_null());
+    let user_data = &*(user_data as *const State);
+    let address = Address::from_raw(address);
+    let key = key_from_raw(key_data, key_len);
+
+    match user_data.0.save_identity(&address, key) {
+        Ok(_) => sys::SG_SUCCESS as c_int,
+        Err(e) => e.code(),
+    }
 }
 
 unsafe extern "C" fn is_trusted_identity(
-    _address: *const sys::signal_protocol_address,
-    _key_data: *mut u8,
-    _key_len: usize,
-    _user_data: *mut c_void,
+    address: *const sys::signal_protocol_address,
+    key_data: *mut u8,
+    key_len: usize,
+    user_data: *mut c_void,
 ) -> c_int {
-    unimplemented!()
+    assert!(!user_data.is_null());
+    let user_data = &*(user_data as *const State);
+    let address = Address::from_raw(address);
+    let key = key_from_raw(key_data, key_len);
+
+    // returns 1 if the identity is trusted, 0 if it isn't
+    match user_data.0.is_trusted_identity(&address, key) {
+        Ok(trusted) => trusted as c_int,
+        Err(e) => e.code(),
+    }
+}
+
+/// `libsignal-protocol-c` passes a null key when an identity is removed.
+unsafe fn key_from_raw<'a>(key_data: *const u8, key_len: usize) -> &'a [u8] {
+    if key_data.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(key_data, key_len)
+    }
 }
 
 unsafe extern "C" fn destroy_func(user_data: *mut c_void) {
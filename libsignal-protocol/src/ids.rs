@@ -0,0 +1,142 @@
+//! Strongly typed IDs, so a pre-key ID can't accidentally be passed where a
+//! signed pre-key or device ID is expected.
+
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+};
+
+/// The ID of a one-time pre-key.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PreKeyId(u32);
+
+impl PreKeyId {
+    pub const fn new(id: u32) -> PreKeyId { PreKeyId(id) }
+
+    pub const fn value(self) -> u32 { self.0 }
+}
+
+impl From<u32> for PreKeyId {
+    fn from(id: u32) -> PreKeyId { PreKeyId(id) }
+}
+
+impl From<PreKeyId> for u32 {
+    fn from(id: PreKeyId) -> u32 { id.0 }
+}
+
+impl Display for PreKeyId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+/// The ID of a signed pre-key.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SignedPreKeyId(u32);
+
+impl SignedPreKeyId {
+    pub const fn new(id: u32) -> SignedPreKeyId { SignedPreKeyId(id) }
+
+    pub const fn value(self) -> u32 { self.0 }
+}
+
+impl From<u32> for SignedPreKeyId {
+    fn from(id: u32) -> SignedPreKeyId { SignedPreKeyId(id) }
+}
+
+impl From<SignedPreKeyId> for u32 {
+    fn from(id: SignedPreKeyId) -> u32 { id.0 }
+}
+
+impl Display for SignedPreKeyId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+/// The ID of one of a user's devices.
+///
+/// `libsignal-protocol-c` stores device IDs as a signed 32-bit integer and
+/// device IDs start at 1, so only `1..=i32::MAX` is valid.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DeviceId(u32);
+
+impl DeviceId {
+    pub const MAX: u32 = 0x7FFF_FFFF;
+
+    pub fn new(id: u32) -> Result<DeviceId, InvalidDeviceId> {
+        if id == 0 || id > DeviceId::MAX {
+            Err(InvalidDeviceId)
+        } else {
+            Ok(DeviceId(id))
+        }
+    }
+
+    /// Wrap a device ID which came from `libsignal-protocol-c`.
+    pub(crate) fn from_raw(id: i32) -> DeviceId { DeviceId(id as u32) }
+
+    pub const fn value(self) -> u32 { self.0 }
+
+    pub(crate) fn raw(self) -> i32 { self.0 as i32 }
+}
+
+impl TryFrom<u32> for DeviceId {
+    type Error = InvalidDeviceId;
+
+    fn try_from(id: u32) -> Result<DeviceId, InvalidDeviceId> {
+        DeviceId::new(id)
+    }
+}
+
+impl TryFrom<i32> for DeviceId {
+    type Error = InvalidDeviceId;
+
+    fn try_from(id: i32) -> Result<DeviceId, InvalidDeviceId> {
+        if id < 0 {
+            Err(InvalidDeviceId)
+        } else {
+            DeviceId::new(id as u32)
+        }
+    }
+}
+
+impl From<DeviceId> for u32 {
+    fn from(id: DeviceId) -> u32 { id.0 }
+}
+
+impl From<DeviceId> for i32 {
+    fn from(id: DeviceId) -> i32 { id.raw() }
+}
+
+impl Display for DeviceId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+/// A device ID was outside the valid range.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, failure_derive::Fail)]
+pub struct InvalidDeviceId;
+
+impl Display for InvalidDeviceId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Device IDs must be between 1 and {}", DeviceId::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_ids_are_validated() {
+        assert_eq!(DeviceId::new(1).unwrap().value(), 1);
+        assert_eq!(DeviceId::new(0), Err(InvalidDeviceId));
+        assert_eq!(DeviceId::new(1 << 31), Err(InvalidDeviceId));
+        assert_eq!(DeviceId::try_from(-1_i32), Err(InvalidDeviceId));
+        assert_eq!(
+            i32::from(DeviceId::new(DeviceId::MAX).unwrap()),
+            0x7FFF_FFFF
+        );
+    }
+}
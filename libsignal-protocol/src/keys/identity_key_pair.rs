@@ -2,10 +2,15 @@ use crate::{
     errors::FromInternalErrorCode,
     keys::{PrivateKey, PublicKey},
     raw_ptr::Raw,
-    Buffer,
+    redact::Redacted,
+    Buffer, Context,
 };
 use failure::Error;
-use std::{io::Write, ptr};
+use std::{
+    fmt::{self, Debug, Formatter},
+    io::Write,
+    ptr,
+};
 
 pub struct IdentityKeyPair {
     pub(crate) raw: Raw<sys::ratchet_identity_key_pair>,
@@ -31,6 +36,33 @@ impl IdentityKeyPair {
         }
     }
 
+    pub fn deserialize(
+        ctx: &Context,
+        data: &[u8],
+    ) -> Result<IdentityKeyPair, Error> {
+        unsafe {
+            IdentityKeyPair::deserialize_with_raw_context(ctx.raw(), data)
+        }
+    }
+
+    unsafe fn deserialize_with_raw_context(
+        ctx: *mut sys::signal_context,
+        data: &[u8],
+    ) -> Result<IdentityKeyPair, Error> {
+        let mut raw = ptr::null_mut();
+        sys::ratchet_identity_key_pair_deserialize(
+            &mut raw,
+            data.as_ptr(),
+            data.len(),
+            ctx,
+        )
+        .into_result()?;
+
+        Ok(IdentityKeyPair {
+            raw: Raw::from_ptr(raw),
+        })
+    }
+
     pub fn serialize_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
         let buffer = self.serialize()?;
         writer.write_all(buffer.as_slice())?;
@@ -61,4 +93,30 @@ impl IdentityKeyPair {
             })
         }
     }
+
+    pub fn private_key(&self) -> Result<PrivateKey, Error> {
+        unsafe {
+            let raw = sys::ratchet_identity_key_pair_get_private(
+                self.raw.as_const_ptr(),
+            );
+            assert!(!raw.is_null());
+            Ok(PrivateKey {
+                raw: Raw::copied_from(raw),
+            })
+        }
+    }
+}
+
+impl_try_from_bytes!(
+    IdentityKeyPair,
+    IdentityKeyPair::deserialize_with_raw_context
+);
+
+impl Debug for IdentityKeyPair {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdentityKeyPair")
+            .field("public_key", &self.public_key().ok())
+            .field("private_key", &Redacted)
+            .finish()
+    }
 }
@@ -2,9 +2,13 @@ use crate::{
     errors::FromInternalErrorCode,
     keys::{PrivateKey, PublicKey},
     raw_ptr::Raw,
+    redact::Redacted,
 };
 use failure::Error;
-use std::ptr;
+use std::{
+    fmt::{self, Debug, Formatter},
+    ptr,
+};
 
 #[derive(Clone)]
 pub struct KeyPair {
@@ -59,3 +63,12 @@ impl KeyPair {
         }
     }
 }
+
+impl Debug for KeyPair {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyPair")
+            .field("public", &self.public().ok())
+            .field("private", &Redacted)
+            .finish()
+    }
+}
@@ -7,7 +7,11 @@ mod public;
 mod signed_pre_key;
 
 pub use self::{
-    identity_key_pair::IdentityKeyPair, key_pair::KeyPair, pre_key::PreKey,
-    pre_key_list::PreKeyList, private::PrivateKey, public::PublicKey,
+    identity_key_pair::IdentityKeyPair,
+    key_pair::KeyPair,
+    pre_key::{PreKey, PreKeyRecord},
+    pre_key_list::PreKeyList,
+    private::PrivateKey,
+    public::PublicKey,
     signed_pre_key::SessionSignedPreKey,
 };
@@ -1,8 +1,13 @@
 use crate::{
-    errors::FromInternalErrorCode, keys::KeyPair, raw_ptr::Raw, Buffer,
+    errors::FromInternalErrorCode, ids::PreKeyId, keys::KeyPair, raw_ptr::Raw,
+    redact::Redacted, Buffer, Context,
 };
 use failure::Error;
-use std::{io::Write, ptr};
+use std::{
+    fmt::{self, Debug, Formatter},
+    io::Write,
+    ptr,
+};
 
 #[derive(Clone)]
 pub struct PreKey {
@@ -10,11 +15,15 @@ pub struct PreKey {
 }
 
 impl PreKey {
-    pub fn new(id: u32, key_pair: &KeyPair) -> Result<PreKey, Error> {
+    pub fn new(id: PreKeyId, key_pair: &KeyPair) -> Result<PreKey, Error> {
         unsafe {
             let mut raw = ptr::null_mut();
-            sys::session_pre_key_create(&mut raw, id, key_pair.raw.as_ptr())
-                .into_result()?;
+            sys::session_pre_key_create(
+                &mut raw,
+                id.value(),
+                key_pair.raw.as_ptr(),
+            )
+            .into_result()?;
 
             Ok(PreKey {
                 raw: Raw::from_ptr(raw),
@@ -22,6 +31,28 @@ impl PreKey {
         }
     }
 
+    pub fn deserialize(ctx: &Context, data: &[u8]) -> Result<PreKey, Error> {
+        unsafe { PreKey::deserialize_with_raw_context(ctx.raw(), data) }
+    }
+
+    unsafe fn deserialize_with_raw_context(
+        ctx: *mut sys::signal_context,
+        data: &[u8],
+    ) -> Result<PreKey, Error> {
+        let mut raw = ptr::null_mut();
+        sys::session_pre_key_deserialize(
+            &mut raw,
+            data.as_ptr(),
+            data.len(),
+            ctx,
+        )
+        .into_result()?;
+
+        Ok(PreKey {
+            raw: Raw::from_ptr(raw),
+        })
+    }
+
     pub fn serialize_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
         let buffer = self.serialize()?;
         writer.write_all(buffer.as_slice())?;
@@ -41,8 +72,10 @@ impl PreKey {
         }
     }
 
-    pub fn id(&self) -> u32 {
-        unsafe { sys::session_pre_key_get_id(self.raw.as_const_ptr()) }
+    pub fn id(&self) -> PreKeyId {
+        unsafe {
+            PreKeyId::new(sys::session_pre_key_get_id(self.raw.as_const_ptr()))
+        }
     }
 
     pub fn key_pair(&self) -> KeyPair {
@@ -56,3 +89,53 @@ impl PreKey {
         }
     }
 }
+
+impl_try_from_bytes!(PreKey, PreKey::deserialize_with_raw_context);
+
+impl Debug for PreKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PreKey")
+            .field("id", &self.id())
+            .field("key_pair", &self.key_pair())
+            .finish()
+    }
+}
+
+/// A freshly generated [`PreKey`] alongside its serialized form, ready to be
+/// handed to a [`PreKeyStore`](crate::PreKeyStore) backend.
+#[derive(Clone)]
+pub struct PreKeyRecord {
+    pre_key: PreKey,
+    serialized: Vec<u8>,
+}
+
+impl PreKeyRecord {
+    pub(crate) fn new(pre_key: PreKey) -> Result<PreKeyRecord, Error> {
+        let serialized = pre_key.serialize()?.as_slice().to_vec();
+
+        Ok(PreKeyRecord {
+            pre_key,
+            serialized,
+        })
+    }
+
+    pub fn id(&self) -> PreKeyId { self.pre_key.id() }
+
+    pub fn pre_key(&self) -> &PreKey { &self.pre_key }
+
+    /// The serialized record, as it should be stored.
+    pub fn serialized(&self) -> &[u8] { &self.serialized }
+
+    pub fn into_parts(self) -> (PreKey, Vec<u8>) {
+        (self.pre_key, self.serialized)
+    }
+}
+
+impl Debug for PreKeyRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PreKeyRecord")
+            .field("pre_key", &self.pre_key)
+            .field("serialized", &Redacted)
+            .finish()
+    }
+}
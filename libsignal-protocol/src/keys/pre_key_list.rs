@@ -1,5 +1,8 @@
 use crate::{keys::PreKey, raw_ptr::Raw};
-use std::marker::PhantomData;
+use std::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+};
 
 pub struct PreKeyList {
     head: *mut sys::signal_protocol_key_helper_pre_key_list_node,
@@ -28,6 +31,12 @@ impl Drop for PreKeyList {
     }
 }
 
+impl Debug for PreKeyList {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
 pub struct PreKeyListIter<'a> {
     _lifetime: PhantomData<&'a ()>,
     head: *mut sys::signal_protocol_key_helper_pre_key_list_node,
@@ -55,3 +64,9 @@ impl<'a> Iterator for PreKeyListIter<'a> {
         }
     }
 }
+
+impl<'a> Debug for PreKeyListIter<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PreKeyListIter").finish()
+    }
+}
@@ -1,13 +1,21 @@
 use crate::{
-    errors::FromInternalErrorCode, keys::PublicKey, raw_ptr::Raw, Context,
+    errors::FromInternalErrorCode, keys::PublicKey, raw_ptr::Raw,
+    redact::Redacted, scrub::Scrubbed, Context,
 };
 use failure::Error;
 use std::{
     cmp::{Ord, Ordering},
-    ptr,
+    fmt::{self, Debug, Formatter},
+    os::raw::c_void,
+    ptr, slice,
 };
 
-#[derive(Clone, Debug)]
+extern "C" {
+    // `curve_calculate_agreement()` allocates the shared secret with malloc()
+    fn free(ptr: *mut c_void);
+}
+
+#[derive(Clone)]
 pub struct PrivateKey {
     pub(crate) raw: Raw<sys::ec_private_key>,
 }
@@ -17,20 +25,20 @@ impl PrivateKey {
         ctx: &Context,
         key: &[u8],
     ) -> Result<PrivateKey, Error> {
-        unsafe {
-            let mut raw = ptr::null_mut();
-            sys::curve_decode_private_point(
-                &mut raw,
-                key.as_ptr(),
-                key.len(),
-                ctx.raw(),
-            )
+        unsafe { PrivateKey::decode_point_with_raw_context(ctx.raw(), key) }
+    }
+
+    unsafe fn decode_point_with_raw_context(
+        ctx: *mut sys::signal_context,
+        key: &[u8],
+    ) -> Result<PrivateKey, Error> {
+        let mut raw = ptr::null_mut();
+        sys::curve_decode_private_point(&mut raw, key.as_ptr(), key.len(), ctx)
             .into_result()?;
 
-            Ok(PrivateKey {
-                raw: Raw::from_ptr(raw),
-            })
-        }
+        Ok(PrivateKey {
+            raw: Raw::from_ptr(raw),
+        })
     }
 
     pub fn generate_public_key(&self) -> Result<PublicKey, Error> {
@@ -44,6 +52,44 @@ impl PrivateKey {
             })
         }
     }
+
+    /// The Diffie-Hellman shared secret between this key and someone else's
+    /// public key.
+    pub(crate) fn calculate_agreement(
+        &self,
+        public_key: &PublicKey,
+    ) -> Result<Scrubbed<Vec<u8>>, Error> {
+        unsafe {
+            let mut data = ptr::null_mut();
+            // returns the length of the shared secret, or a negative error
+            // code
+            let len = sys::curve_calculate_agreement(
+                &mut data,
+                public_key.raw.as_const_ptr(),
+                self.raw.as_const_ptr(),
+            );
+            if len < 0 {
+                len.into_result()?;
+            }
+            assert!(!data.is_null());
+
+            let shared = slice::from_raw_parts_mut(data, len as usize);
+            let secret = Scrubbed(shared.to_vec());
+            // zero libsignal-protocol-c's copy before handing it back
+            drop(Scrubbed(shared));
+            free(data as *mut c_void);
+
+            Ok(secret)
+        }
+    }
+}
+
+impl_try_from_bytes!(PrivateKey, PrivateKey::decode_point_with_raw_context);
+
+impl Debug for PrivateKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PrivateKey").field(&Redacted).finish()
+    }
 }
 
 impl Ord for PrivateKey {
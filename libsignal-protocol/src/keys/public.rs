@@ -1,36 +1,38 @@
 use crate::{
     errors::{FromInternalErrorCode, InternalError},
     raw_ptr::Raw,
+    redact::ShortHex,
     Buffer, Context,
 };
 use failure::Error;
 use std::{
     cmp::{Ord, Ordering},
+    fmt::{self, Debug, Formatter},
     io::Write,
     ptr,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct PublicKey {
     pub(crate) raw: Raw<sys::ec_public_key>,
 }
 
 impl PublicKey {
     pub fn decode_point(ctx: &Context, key: &[u8]) -> Result<PublicKey, Error> {
-        unsafe {
-            let mut raw = ptr::null_mut();
-            sys::curve_decode_point(
-                &mut raw,
-                key.as_ptr(),
-                key.len(),
-                ctx.raw(),
-            )
+        unsafe { PublicKey::decode_point_with_raw_context(ctx.raw(), key) }
+    }
+
+    unsafe fn decode_point_with_raw_context(
+        ctx: *mut sys::signal_context,
+        key: &[u8],
+    ) -> Result<PublicKey, Error> {
+        let mut raw = ptr::null_mut();
+        sys::curve_decode_point(&mut raw, key.as_ptr(), key.len(), ctx)
             .into_result()?;
 
-            Ok(PublicKey {
-                raw: Raw::from_ptr(raw),
-            })
-        }
+        Ok(PublicKey {
+            raw: Raw::from_ptr(raw),
+        })
     }
 
     pub fn serialize<W: Write>(&self, mut writer: W) -> Result<(), Error> {
@@ -73,6 +75,19 @@ impl PublicKey {
     }
 }
 
+impl_try_from_bytes!(PublicKey, PublicKey::decode_point_with_raw_context);
+
+impl Debug for PublicKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut serialized = Vec::new();
+        self.serialize(&mut serialized).map_err(|_| fmt::Error)?;
+
+        f.debug_tuple("PublicKey")
+            .field(&ShortHex(&serialized))
+            .finish()
+    }
+}
+
 impl Ord for PublicKey {
     fn cmp(&self, other: &PublicKey) -> Ordering {
         let cmp = unsafe {
@@ -1,11 +1,12 @@
 use crate::{
-    errors::FromInternalErrorCode, keys::KeyPair, raw_ptr::Raw, Buffer,
+    errors::FromInternalErrorCode, ids::SignedPreKeyId, keys::KeyPair,
+    raw_ptr::Raw, timestamp::Timestamp, Buffer, Context,
 };
 use failure::Error;
 use std::{
+    fmt::{self, Debug, Formatter},
     io::Write,
     ptr,
-    time::{Duration, SystemTime},
 };
 
 pub struct SessionSignedPreKey {
@@ -14,19 +15,17 @@ pub struct SessionSignedPreKey {
 
 impl SessionSignedPreKey {
     pub fn new(
-        id: u32,
-        timestamp: SystemTime,
+        id: SignedPreKeyId,
+        timestamp: Timestamp,
         key_pair: &KeyPair,
         signature: &[u8],
     ) -> Result<SessionSignedPreKey, Error> {
         unsafe {
             let mut raw = ptr::null_mut();
-            let elapsed = timestamp.duration_since(SystemTime::UNIX_EPOCH)?;
-
             sys::session_signed_pre_key_create(
                 &mut raw,
-                id,
-                elapsed.as_secs(),
+                id.value(),
+                timestamp.as_millis(),
                 key_pair.raw.as_ptr(),
                 signature.as_ptr(),
                 signature.len(),
@@ -39,6 +38,33 @@ impl SessionSignedPreKey {
         }
     }
 
+    pub fn deserialize(
+        ctx: &Context,
+        data: &[u8],
+    ) -> Result<SessionSignedPreKey, Error> {
+        unsafe {
+            SessionSignedPreKey::deserialize_with_raw_context(ctx.raw(), data)
+        }
+    }
+
+    unsafe fn deserialize_with_raw_context(
+        ctx: *mut sys::signal_context,
+        data: &[u8],
+    ) -> Result<SessionSignedPreKey, Error> {
+        let mut raw = ptr::null_mut();
+        sys::session_signed_pre_key_deserialize(
+            &mut raw,
+            data.as_ptr(),
+            data.len(),
+            ctx,
+        )
+        .into_result()?;
+
+        Ok(SessionSignedPreKey {
+            raw: Raw::from_ptr(raw),
+        })
+    }
+
     pub fn serialize_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
         let buffer = self.serialize()?;
         writer.write_all(buffer.as_slice())?;
@@ -58,16 +84,20 @@ impl SessionSignedPreKey {
         }
     }
 
-    pub fn id(&self) -> u32 {
-        unsafe { sys::session_signed_pre_key_get_id(self.raw.as_const_ptr()) }
+    pub fn id(&self) -> SignedPreKeyId {
+        unsafe {
+            SignedPreKeyId::new(sys::session_signed_pre_key_get_id(
+                self.raw.as_const_ptr(),
+            ))
+        }
     }
 
-    pub fn timestamp(&self) -> SystemTime {
+    /// When this signed pre-key was generated.
+    pub fn timestamp(&self) -> Timestamp {
         unsafe {
-            let ts = sys::session_signed_pre_key_get_timestamp(
+            Timestamp::from_millis(sys::session_signed_pre_key_get_timestamp(
                 self.raw.as_const_ptr(),
-            );
-            SystemTime::UNIX_EPOCH + Duration::from_secs(ts)
+            ))
         }
     }
 
@@ -96,3 +126,18 @@ impl SessionSignedPreKey {
         }
     }
 }
+
+impl_try_from_bytes!(
+    SessionSignedPreKey,
+    SessionSignedPreKey::deserialize_with_raw_context
+);
+
+impl Debug for SessionSignedPreKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionSignedPreKey")
+            .field("id", &self.id())
+            .field("timestamp", &self.timestamp())
+            .field("key_pair", &self.get_key_pair())
+            .finish()
+    }
+}
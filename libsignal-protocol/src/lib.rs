@@ -20,15 +20,17 @@
 //! which is then used for all subsequent encrypt/decrypt operations. There is
 //! no need to ever tear down a session once one has been established.
 //!
-//! Sessions are established in one of three ways:
+//! Sessions are established in one of two ways:
 //!
 //! 1. [`PreKeyBundle`]. A client that wishes to send a message to a recipient
 //!    can establish a session by retrieving a PreKeyBundle for that recipient
-//!    from the server.
-//! 2. PreKeySignalMessages.  A client can receive a PreKeySignalMessage from a
-//!    recipient and use it to establish a session.
-//! 3. KeyExchangeMessages.  Two clients can exchange KeyExchange messages to
-//!    establish a session.
+//!    from the server (or directly from the recipient, as a [`ContactPacket`]).
+//! 2. [`PreKeySignalMessage`]s. A client can receive a PreKeySignalMessage from
+//!    a recipient and use it to establish a session.
+//!
+//! Older versions of the protocol also let two clients establish a session by
+//! exchanging KeyExchangeMessages. That was removed in version 3, and
+//! `libsignal-protocol-c` doesn't support it.
 //!
 //! ## State
 //!
@@ -52,34 +54,103 @@
 
 extern crate libsignal_protocol_sys as sys;
 
+#[macro_use]
+mod macros;
+
 pub use crate::{
     address::Address,
+    attachment::AttachmentKeys,
+    backup_keys::{BackupKeys, BACKUP_CIPHER_KEY_LABEL, BACKUP_MAC_KEY_LABEL},
     buffer::Buffer,
+    bundle_cache::BundleCache,
+    bundle_fetcher::BundleFetcher,
+    contact_packet::ContactPacket,
     context::Context,
     crypto::{CipherMode, Crypto, SignalCipherType, SignalCipherTypeError},
+    device_consistency::{
+        DeviceConsistencyCommitment, DeviceConsistencyMessage,
+        DeviceConsistencySignature,
+    },
     errors::InternalError,
+    fingerprint::{DisplayableFingerprint, Fingerprint, ScannableFingerprint},
+    group_cipher::GroupCipher,
+    group_session_builder::GroupSessionBuilder,
     hkdf::HMACBasedKeyDerivationFunction,
     identity_key_store::IdentityKeyStore,
-    pre_key_bundle::{PreKeyBundle, PreKeyBundleBuilder},
+    ids::{DeviceId, InvalidDeviceId, PreKeyId, SignedPreKeyId},
+    keys::{
+        IdentityKeyPair, KeyPair, PreKey, PreKeyRecord, PrivateKey, PublicKey,
+        SessionSignedPreKey,
+    },
+    messages::{
+        CiphertextMessage, CiphertextType, PreKeySignalMessage,
+        SenderKeyDistributionMessage, SenderKeyMessage, SignalMessage,
+    },
+    metadata_key::MetadataKey,
+    padding::Padding,
+    pre_key_bundle::{PreKeyBundle, PreKeyBundleBuilder, PreKeyBundleError},
+    pre_key_exhaustion_guard::PreKeyExhaustionGuard,
     pre_key_store::PreKeyStore,
+    sender_key_name::SenderKeyName,
+    sender_key_store::SenderKeyStore,
     session_builder::SessionBuilder,
+    session_cipher::SessionCipher,
+    session_record::SessionRecord,
+    session_state::SessionState,
     session_store::SessionStore,
+    signed_payload::SignedPayload,
     signed_pre_key_store::SignedPreKeyStore,
+    sniff::{sniff, MessageKind},
     store_context::StoreContext,
+    timestamp::Timestamp,
 };
 
+#[cfg(feature = "identity-export")]
+pub use crate::identity_export::IdentityExport;
+
 mod address;
+mod attachment;
+mod backup_keys;
 mod buffer;
+mod bundle_cache;
+mod bundle_fetcher;
+mod contact_packet;
 mod context;
 pub mod crypto;
+pub mod device_consistency;
 mod errors;
+mod fingerprint;
+mod group_cipher;
+mod group_session_builder;
 mod hkdf;
+#[cfg(feature = "identity-export")]
+mod identity_export;
 mod identity_key_store;
+mod ids;
 pub mod keys;
+pub mod messages;
+mod metadata_key;
+mod padding;
 mod pre_key_bundle;
+mod pre_key_exhaustion_guard;
 mod pre_key_store;
+pub mod prelude;
 mod raw_ptr;
+mod redact;
+mod scrub;
+#[cfg(feature = "secrecy-support")]
+mod secrets;
+mod sender_key_name;
+mod sender_key_store;
 mod session_builder;
+mod session_cipher;
+mod session_record;
+mod session_state;
 mod session_store;
+mod signed_payload;
 mod signed_pre_key_store;
+mod sniff;
 mod store_context;
+mod timestamp;
+#[cfg(feature = "xeddsa")]
+pub mod xeddsa;
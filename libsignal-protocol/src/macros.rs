@@ -0,0 +1,24 @@
+/// Implement `TryFrom<&[u8]>` and `TryFrom<Vec<u8>>` for a type which can be
+/// deserialized with an `unsafe fn(*mut sys::signal_context, &[u8])`.
+///
+/// The [`Context`](crate::Context) is only used by `libsignal-protocol-c` for
+/// logging, so it's safe to pass a null pointer when deserializing.
+macro_rules! impl_try_from_bytes {
+    ($type:ty, $deserialize:path) => {
+        impl std::convert::TryFrom<&[u8]> for $type {
+            type Error = failure::Error;
+
+            fn try_from(data: &[u8]) -> Result<$type, failure::Error> {
+                unsafe { $deserialize(std::ptr::null_mut(), data) }
+            }
+        }
+
+        impl std::convert::TryFrom<Vec<u8>> for $type {
+            type Error = failure::Error;
+
+            fn try_from(data: Vec<u8>) -> Result<$type, failure::Error> {
+                <$type as std::convert::TryFrom<&[u8]>>::try_from(&data)
+            }
+        }
+    };
+}
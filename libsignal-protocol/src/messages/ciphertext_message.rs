@@ -0,0 +1,207 @@
+use crate::{
+    errors::InternalError,
+    messages::{
+        PreKeySignalMessage, SenderKeyDistributionMessage, SenderKeyMessage,
+        SignalMessage,
+    },
+    raw_ptr::Raw,
+    sniff::{sniff, MessageKind},
+    Context,
+};
+use failure::Error;
+
+/// The kinds of message [`CiphertextMessage`] may contain.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CiphertextType {
+    /// A [`SignalMessage`].
+    Signal,
+    /// A [`PreKeySignalMessage`].
+    PreKey,
+    /// A [`SenderKeyMessage`].
+    SenderKey,
+    /// A [`SenderKeyDistributionMessage`].
+    SenderKeyDistribution,
+}
+
+/// Any of the encrypted messages the protocol sends.
+#[derive(Debug, Clone)]
+pub enum CiphertextMessage {
+    Signal(SignalMessage),
+    PreKey(PreKeySignalMessage),
+    SenderKey(SenderKeyMessage),
+    SenderKeyDistribution(SenderKeyDistributionMessage),
+}
+
+impl CiphertextMessage {
+    /// Parse a message, working out which kind it is with [`sniff()`].
+    ///
+    /// [`SenderKeyDistributionMessage`]s can't be told apart from
+    /// [`SenderKeyMessage`]s by looking at them, and are normally delivered
+    /// inside a [`SignalMessage`] anyway, so they are never detected. Use
+    /// [`CiphertextMessage::deserialize_as()`] when the kind of message is
+    /// already known (e.g. from the envelope it arrived in).
+    pub fn deserialize(
+        ctx: &Context,
+        data: &[u8],
+    ) -> Result<CiphertextMessage, Error> {
+        let ty = match sniff(data) {
+            MessageKind::Signal => CiphertextType::Signal,
+            MessageKind::PreKeySignal => CiphertextType::PreKey,
+            MessageKind::SenderKey => CiphertextType::SenderKey,
+            MessageKind::SealedSender | MessageKind::Unknown => {
+                return Err(InternalError::InvalidMessage.into());
+            },
+        };
+
+        CiphertextMessage::deserialize_as(ctx, ty, data)
+    }
+
+    /// Parse a message whose kind is already known.
+    pub fn deserialize_as(
+        ctx: &Context,
+        ty: CiphertextType,
+        data: &[u8],
+    ) -> Result<CiphertextMessage, Error> {
+        match ty {
+            CiphertextType::Signal => {
+                SignalMessage::deserialize(ctx, data).map(Into::into)
+            },
+            CiphertextType::PreKey => {
+                PreKeySignalMessage::deserialize(ctx, data).map(Into::into)
+            },
+            CiphertextType::SenderKey => {
+                SenderKeyMessage::deserialize(ctx, data).map(Into::into)
+            },
+            CiphertextType::SenderKeyDistribution => {
+                SenderKeyDistributionMessage::deserialize(ctx, data)
+                    .map(Into::into)
+            },
+        }
+    }
+
+    /// Take ownership of a message created by `libsignal-protocol-c`.
+    pub(crate) unsafe fn from_raw(
+        raw: *mut sys::ciphertext_message,
+    ) -> CiphertextMessage {
+        assert!(!raw.is_null());
+
+        // every message type starts with a ciphertext_message, so we can
+        // cast to the concrete type
+        match sys::ciphertext_message_get_type(raw) as u32 {
+            sys::CIPHERTEXT_SIGNAL_TYPE => {
+                CiphertextMessage::Signal(SignalMessage {
+                    raw: Raw::from_ptr(raw as *mut _),
+                })
+            },
+            sys::CIPHERTEXT_PREKEY_TYPE => {
+                CiphertextMessage::PreKey(PreKeySignalMessage {
+                    raw: Raw::from_ptr(raw as *mut _),
+                })
+            },
+            sys::CIPHERTEXT_SENDERKEY_TYPE => {
+                CiphertextMessage::SenderKey(SenderKeyMessage {
+                    raw: Raw::from_ptr(raw as *mut _),
+                })
+            },
+            sys::CIPHERTEXT_SENDERKEY_DISTRIBUTION_TYPE => {
+                CiphertextMessage::SenderKeyDistribution(
+                    SenderKeyDistributionMessage {
+                        raw: Raw::from_ptr(raw as *mut _),
+                    },
+                )
+            },
+            other => unreachable!("Unknown ciphertext message type: {}", other),
+        }
+    }
+
+    pub fn get_type(&self) -> CiphertextType {
+        match self {
+            CiphertextMessage::Signal(_) => CiphertextType::Signal,
+            CiphertextMessage::PreKey(_) => CiphertextType::PreKey,
+            CiphertextMessage::SenderKey(_) => CiphertextType::SenderKey,
+            CiphertextMessage::SenderKeyDistribution(_) => {
+                CiphertextType::SenderKeyDistribution
+            },
+        }
+    }
+
+    /// The bytes to send over the wire.
+    pub fn serialize(&self) -> &[u8] {
+        match self {
+            CiphertextMessage::Signal(message) => message.serialize(),
+            CiphertextMessage::PreKey(message) => message.serialize(),
+            CiphertextMessage::SenderKey(message) => message.serialize(),
+            CiphertextMessage::SenderKeyDistribution(message) => {
+                message.serialize()
+            },
+        }
+    }
+}
+
+impl From<SignalMessage> for CiphertextMessage {
+    fn from(message: SignalMessage) -> CiphertextMessage {
+        CiphertextMessage::Signal(message)
+    }
+}
+
+impl From<PreKeySignalMessage> for CiphertextMessage {
+    fn from(message: PreKeySignalMessage) -> CiphertextMessage {
+        CiphertextMessage::PreKey(message)
+    }
+}
+
+impl From<SenderKeyMessage> for CiphertextMessage {
+    fn from(message: SenderKeyMessage) -> CiphertextMessage {
+        CiphertextMessage::SenderKey(message)
+    }
+}
+
+impl From<SenderKeyDistributionMessage> for CiphertextMessage {
+    fn from(message: SenderKeyDistributionMessage) -> CiphertextMessage {
+        CiphertextMessage::SenderKeyDistribution(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::test_utils;
+
+    #[test]
+    fn detect_the_message_type() {
+        let ctx = Context::default();
+        let key = ctx.generate_key_pair().unwrap().public().unwrap();
+        let signal = test_utils::signal_message(&key, 7, b"ciphertext");
+        let pre_key =
+            test_utils::pre_key_signal_message(None, &key, &key, 42, &signal);
+        let sender_key = test_utils::sender_key_message(3, 9, b"ciphertext");
+
+        let inputs = vec![
+            (signal, CiphertextType::Signal),
+            (pre_key, CiphertextType::PreKey),
+            (sender_key, CiphertextType::SenderKey),
+        ];
+
+        for (serialized, should_be) in inputs {
+            let got =
+                CiphertextMessage::deserialize(&ctx, &serialized).unwrap();
+
+            assert_eq!(got.get_type(), should_be);
+            assert_eq!(got.serialize(), &serialized[..]);
+        }
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        let ctx = Context::default();
+
+        let err = CiphertextMessage::deserialize(&ctx, &[0x42, 0x42])
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            err.downcast_ref::<InternalError>(),
+            Some(&InternalError::InvalidMessage)
+        );
+    }
+}
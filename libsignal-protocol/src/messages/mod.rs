@@ -0,0 +1,114 @@
+//! The messages exchanged by the protocol.
+
+mod ciphertext_message;
+mod pre_key_signal_message;
+mod sender_key_distribution_message;
+mod sender_key_message;
+mod signal_message;
+
+pub use self::{
+    ciphertext_message::{CiphertextMessage, CiphertextType},
+    pre_key_signal_message::PreKeySignalMessage,
+    sender_key_distribution_message::SenderKeyDistributionMessage,
+    sender_key_message::SenderKeyMessage,
+    signal_message::SignalMessage,
+};
+
+/// Get the serialized form `libsignal-protocol-c` keeps alongside a
+/// message's fields.
+///
+/// # Safety
+///
+/// `message` must point to one of the `libsignal-protocol-c` message types,
+/// all of which start with a `ciphertext_message`.
+pub(crate) unsafe fn serialized<'a, T>(message: *const T) -> &'a [u8] {
+    let buffer = sys::ciphertext_message_get_serialized(
+        message as *const sys::ciphertext_message,
+    );
+    assert!(!buffer.is_null());
+
+    std::slice::from_raw_parts(
+        sys::signal_buffer_data(buffer),
+        sys::signal_buffer_len(buffer),
+    )
+}
+
+/// Hand-assembled messages for tests, because producing real ones requires
+/// an established session.
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use crate::keys::PublicKey;
+
+    const VERSION: u8 = 0x33;
+    const MAC_LEN: usize = 8;
+    const SIGNATURE_LEN: usize = 64;
+
+    pub(crate) fn signal_message(
+        ratchet_key: &PublicKey,
+        counter: u8,
+        ciphertext: &[u8],
+    ) -> Vec<u8> {
+        let mut message = vec![VERSION];
+        push_key(&mut message, 1, ratchet_key);
+        push_varint(&mut message, 2, counter);
+        push_varint(&mut message, 3, 0);
+        push_bytes(&mut message, 4, ciphertext);
+        message.extend_from_slice(&[0; MAC_LEN]);
+
+        message
+    }
+
+    /// Assemble a pre-key message whose signed pre-key ID is always 5.
+    pub(crate) fn pre_key_signal_message(
+        pre_key_id: Option<u8>,
+        base_key: &PublicKey,
+        identity_key: &PublicKey,
+        registration_id: u8,
+        signal_message: &[u8],
+    ) -> Vec<u8> {
+        let mut message = vec![VERSION];
+        if let Some(id) = pre_key_id {
+            push_varint(&mut message, 1, id);
+        }
+        push_key(&mut message, 2, base_key);
+        push_key(&mut message, 3, identity_key);
+        push_bytes(&mut message, 4, signal_message);
+        push_varint(&mut message, 5, registration_id);
+        push_varint(&mut message, 6, 5);
+
+        message
+    }
+
+    pub(crate) fn sender_key_message(
+        key_id: u8,
+        iteration: u8,
+        ciphertext: &[u8],
+    ) -> Vec<u8> {
+        let mut message = vec![VERSION];
+        push_varint(&mut message, 1, key_id);
+        push_varint(&mut message, 2, iteration);
+        push_bytes(&mut message, 3, ciphertext);
+        message.extend_from_slice(&[0; SIGNATURE_LEN]);
+
+        message
+    }
+
+    // the values are all small, so every length and varint fits in a byte
+
+    fn push_varint(buffer: &mut Vec<u8>, field: u8, value: u8) {
+        assert!(value < 0x80);
+        buffer.extend_from_slice(&[field << 3, value]);
+    }
+
+    fn push_bytes(buffer: &mut Vec<u8>, field: u8, value: &[u8]) {
+        assert!(value.len() < 0x80);
+        buffer.extend_from_slice(&[field << 3 | 2, value.len() as u8]);
+        buffer.extend_from_slice(value);
+    }
+
+    fn push_key(buffer: &mut Vec<u8>, field: u8, key: &PublicKey) {
+        let mut serialized = Vec::new();
+        key.serialize(&mut serialized).unwrap();
+        push_bytes(buffer, field, &serialized);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{
+    errors::FromInternalErrorCode,
+    ids::{PreKeyId, SignedPreKeyId},
+    keys::PublicKey,
+    messages::SignalMessage,
+    raw_ptr::Raw,
+    Context,
+};
+use failure::Error;
+use std::{
+    fmt::{self, Debug, Formatter},
+    ptr,
+};
+
+/// A message which also carries the information needed to set up a session
+/// with its sender.
+#[derive(Clone)]
+pub struct PreKeySignalMessage {
+    pub(crate) raw: Raw<sys::pre_key_signal_message>,
+}
+
+impl PreKeySignalMessage {
+    pub fn deserialize(
+        ctx: &Context,
+        data: &[u8],
+    ) -> Result<PreKeySignalMessage, Error> {
+        unsafe {
+            PreKeySignalMessage::deserialize_with_raw_context(ctx.raw(), data)
+        }
+    }
+
+    unsafe fn deserialize_with_raw_context(
+        ctx: *mut sys::signal_context,
+        data: &[u8],
+    ) -> Result<PreKeySignalMessage, Error> {
+        let mut raw = ptr::null_mut();
+        sys::pre_key_signal_message_deserialize(
+            &mut raw,
+            data.as_ptr(),
+            data.len(),
+            ctx,
+        )
+        .into_result()?;
+
+        Ok(PreKeySignalMessage {
+            raw: Raw::from_ptr(raw),
+        })
+    }
+
+    /// The bytes to send over the wire.
+    pub fn serialize(&self) -> &[u8] {
+        unsafe { super::serialized(self.raw.as_const_ptr()) }
+    }
+
+    pub fn message_version(&self) -> u8 {
+        unsafe {
+            sys::pre_key_signal_message_get_message_version(
+                self.raw.as_const_ptr(),
+            )
+        }
+    }
+
+    /// The sender's identity key.
+    pub fn identity_key(&self) -> PublicKey {
+        unsafe {
+            let raw = sys::pre_key_signal_message_get_identity_key(
+                self.raw.as_const_ptr(),
+            );
+            assert!(!raw.is_null());
+            PublicKey {
+                raw: Raw::copied_from(raw),
+            }
+        }
+    }
+
+    /// The sender's registration ID.
+    pub fn registration_id(&self) -> u32 {
+        unsafe {
+            sys::pre_key_signal_message_get_registration_id(
+                self.raw.as_const_ptr(),
+            )
+        }
+    }
+
+    /// The ID of our one-time pre-key the sender used, if any.
+    pub fn pre_key_id(&self) -> Option<PreKeyId> {
+        unsafe {
+            let raw = self.raw.as_const_ptr();
+
+            if sys::pre_key_signal_message_has_pre_key_id(raw) != 0 {
+                Some(PreKeyId::new(sys::pre_key_signal_message_get_pre_key_id(
+                    raw,
+                )))
+            } else {
+                None
+            }
+        }
+    }
+
+    /// The ID of our signed pre-key the sender used.
+    pub fn signed_pre_key_id(&self) -> SignedPreKeyId {
+        unsafe {
+            SignedPreKeyId::new(
+                sys::pre_key_signal_message_get_signed_pre_key_id(
+                    self.raw.as_const_ptr(),
+                ),
+            )
+        }
+    }
+
+    /// The sender's ephemeral base key.
+    pub fn base_key(&self) -> PublicKey {
+        unsafe {
+            let raw = sys::pre_key_signal_message_get_base_key(
+                self.raw.as_const_ptr(),
+            );
+            assert!(!raw.is_null());
+            PublicKey {
+                raw: Raw::copied_from(raw),
+            }
+        }
+    }
+
+    /// The [`SignalMessage`] carried inside this message.
+    pub fn signal_message(&self) -> SignalMessage {
+        unsafe {
+            let raw = sys::pre_key_signal_message_get_signal_message(
+                self.raw.as_const_ptr(),
+            );
+            assert!(!raw.is_null());
+            SignalMessage {
+                raw: Raw::copied_from(raw),
+            }
+        }
+    }
+}
+
+impl_try_from_bytes!(
+    PreKeySignalMessage,
+    PreKeySignalMessage::deserialize_with_raw_context
+);
+
+impl Debug for PreKeySignalMessage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PreKeySignalMessage")
+            .field("message_version", &self.message_version())
+            .field("registration_id", &self.registration_id())
+            .field("pre_key_id", &self.pre_key_id())
+            .field("signed_pre_key_id", &self.signed_pre_key_id())
+            .field("identity_key", &self.identity_key())
+            .field("base_key", &self.base_key())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::test_utils;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn inspect_a_pre_key_signal_message() {
+        let ctx = Context::default();
+        let identity_key = ctx.generate_key_pair().unwrap().public().unwrap();
+        let base_key = ctx.generate_key_pair().unwrap().public().unwrap();
+        let serialized = test_utils::pre_key_signal_message(
+            Some(31),
+            &base_key,
+            &identity_key,
+            42,
+            &test_utils::signal_message(&base_key, 7, b"ciphertext"),
+        );
+
+        let message = PreKeySignalMessage::try_from(&serialized[..]).unwrap();
+
+        assert_eq!(message.message_version(), 3);
+        assert_eq!(message.registration_id(), 42);
+        assert_eq!(message.pre_key_id(), Some(PreKeyId::new(31)));
+        assert_eq!(message.signed_pre_key_id(), SignedPreKeyId::new(5));
+        assert_eq!(message.identity_key(), identity_key);
+        assert_eq!(message.base_key(), base_key);
+        assert_eq!(message.serialize(), &serialized[..]);
+    }
+
+    #[test]
+    fn the_one_time_pre_key_is_optional() {
+        let ctx = Context::default();
+        let key = ctx.generate_key_pair().unwrap().public().unwrap();
+        let serialized = test_utils::pre_key_signal_message(
+            None,
+            &key,
+            &key,
+            42,
+            &test_utils::signal_message(&key, 7, b"ciphertext"),
+        );
+
+        let message = PreKeySignalMessage::try_from(serialized).unwrap();
+
+        assert_eq!(message.pre_key_id(), None);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{
+    errors::FromInternalErrorCode, keys::PublicKey, raw_ptr::Raw,
+    redact::Redacted, Context,
+};
+use failure::Error;
+use std::{
+    fmt::{self, Debug, Formatter},
+    ptr,
+};
+
+/// Hands a sender key to the other members of a group, so they can decrypt
+/// the sender's [`SenderKeyMessage`](super::SenderKeyMessage)s.
+///
+/// This contains secret key material, so it must only ever be sent inside
+/// an encrypted one-to-one message.
+#[derive(Clone)]
+pub struct SenderKeyDistributionMessage {
+    pub(crate) raw: Raw<sys::sender_key_distribution_message>,
+}
+
+impl SenderKeyDistributionMessage {
+    pub fn deserialize(
+        ctx: &Context,
+        data: &[u8],
+    ) -> Result<SenderKeyDistributionMessage, Error> {
+        unsafe {
+            SenderKeyDistributionMessage::deserialize_with_raw_context(
+                ctx.raw(),
+                data,
+            )
+        }
+    }
+
+    unsafe fn deserialize_with_raw_context(
+        ctx: *mut sys::signal_context,
+        data: &[u8],
+    ) -> Result<SenderKeyDistributionMessage, Error> {
+        let mut raw = ptr::null_mut();
+        sys::sender_key_distribution_message_deserialize(
+            &mut raw,
+            data.as_ptr(),
+            data.len(),
+            ctx,
+        )
+        .into_result()?;
+
+        Ok(SenderKeyDistributionMessage {
+            raw: Raw::from_ptr(raw),
+        })
+    }
+
+    /// The bytes to send (encrypted) to the rest of the group.
+    pub fn serialize(&self) -> &[u8] {
+        unsafe { super::serialized(self.raw.as_const_ptr()) }
+    }
+
+    /// The ID of the sender key being distributed.
+    pub fn id(&self) -> u32 {
+        unsafe {
+            sys::sender_key_distribution_message_get_id(self.raw.as_ptr())
+        }
+    }
+
+    pub fn iteration(&self) -> u32 {
+        unsafe {
+            sys::sender_key_distribution_message_get_iteration(
+                self.raw.as_ptr(),
+            )
+        }
+    }
+
+    pub fn chain_key(&self) -> &[u8] {
+        unsafe {
+            // the buffer is owned by the message, so we mustn't free it
+            let buffer = sys::sender_key_distribution_message_get_chain_key(
+                self.raw.as_ptr(),
+            );
+            assert!(!buffer.is_null());
+
+            std::slice::from_raw_parts(
+                sys::signal_buffer_data(buffer),
+                sys::signal_buffer_len(buffer),
+            )
+        }
+    }
+
+    /// The key the sender's group messages are signed with.
+    pub fn signature_key(&self) -> PublicKey {
+        unsafe {
+            let raw = sys::sender_key_distribution_message_get_signature_key(
+                self.raw.as_ptr(),
+            );
+            assert!(!raw.is_null());
+            PublicKey {
+                raw: Raw::copied_from(raw),
+            }
+        }
+    }
+}
+
+impl_try_from_bytes!(
+    SenderKeyDistributionMessage,
+    SenderKeyDistributionMessage::deserialize_with_raw_context
+);
+
+impl Debug for SenderKeyDistributionMessage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SenderKeyDistributionMessage")
+            .field("id", &self.id())
+            .field("iteration", &self.iteration())
+            .field("chain_key", &Redacted)
+            .field("signature_key", &self.signature_key())
+            .finish()
+    }
+}
@@ -0,0 +1,121 @@
+use crate::{
+    errors::FromInternalErrorCode, keys::PublicKey, raw_ptr::Raw, Context,
+};
+use failure::Error;
+use std::{
+    fmt::{self, Debug, Formatter},
+    ptr,
+};
+
+/// A group message, encrypted with the sender's sender key.
+#[derive(Clone)]
+pub struct SenderKeyMessage {
+    pub(crate) raw: Raw<sys::sender_key_message>,
+}
+
+impl SenderKeyMessage {
+    pub fn deserialize(
+        ctx: &Context,
+        data: &[u8],
+    ) -> Result<SenderKeyMessage, Error> {
+        unsafe {
+            SenderKeyMessage::deserialize_with_raw_context(ctx.raw(), data)
+        }
+    }
+
+    unsafe fn deserialize_with_raw_context(
+        ctx: *mut sys::signal_context,
+        data: &[u8],
+    ) -> Result<SenderKeyMessage, Error> {
+        let mut raw = ptr::null_mut();
+        sys::sender_key_message_deserialize(
+            &mut raw,
+            data.as_ptr(),
+            data.len(),
+            ctx,
+        )
+        .into_result()?;
+
+        Ok(SenderKeyMessage {
+            raw: Raw::from_ptr(raw),
+        })
+    }
+
+    /// The bytes to send over the wire.
+    pub fn serialize(&self) -> &[u8] {
+        unsafe { super::serialized(self.raw.as_const_ptr()) }
+    }
+
+    /// The ID of the sender key this message was encrypted with.
+    pub fn key_id(&self) -> u32 {
+        unsafe { sys::sender_key_message_get_key_id(self.raw.as_ptr()) }
+    }
+
+    pub fn iteration(&self) -> u32 {
+        unsafe { sys::sender_key_message_get_iteration(self.raw.as_ptr()) }
+    }
+
+    pub fn ciphertext(&self) -> &[u8] {
+        unsafe {
+            // the buffer is owned by the message, so we mustn't free it
+            let buffer =
+                sys::sender_key_message_get_ciphertext(self.raw.as_ptr());
+            assert!(!buffer.is_null());
+
+            std::slice::from_raw_parts(
+                sys::signal_buffer_data(buffer),
+                sys::signal_buffer_len(buffer),
+            )
+        }
+    }
+
+    /// Check the message was signed by the sender's signature key.
+    pub fn verify_signature(
+        &self,
+        signature_key: &PublicKey,
+    ) -> Result<(), Error> {
+        unsafe {
+            sys::sender_key_message_verify_signature(
+                self.raw.as_ptr(),
+                signature_key.raw.as_ptr(),
+            )
+            .into_result()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl_try_from_bytes!(
+    SenderKeyMessage,
+    SenderKeyMessage::deserialize_with_raw_context
+);
+
+impl Debug for SenderKeyMessage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SenderKeyMessage")
+            .field("key_id", &self.key_id())
+            .field("iteration", &self.iteration())
+            .field("ciphertext_len", &self.ciphertext().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::test_utils;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn inspect_a_sender_key_message() {
+        let serialized = test_utils::sender_key_message(3, 9, b"ciphertext");
+
+        let message = SenderKeyMessage::try_from(&serialized[..]).unwrap();
+
+        assert_eq!(message.key_id(), 3);
+        assert_eq!(message.iteration(), 9);
+        assert_eq!(message.ciphertext(), b"ciphertext");
+        assert_eq!(message.serialize(), &serialized[..]);
+    }
+}
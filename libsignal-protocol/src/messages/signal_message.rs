@@ -0,0 +1,184 @@
+use crate::{
+    errors::{FromInternalErrorCode, InternalError},
+    keys::PublicKey,
+    raw_ptr::Raw,
+    Context,
+};
+use failure::Error;
+use std::{
+    fmt::{self, Debug, Formatter},
+    ptr,
+};
+
+/// A message sent within an established session.
+#[derive(Clone)]
+pub struct SignalMessage {
+    pub(crate) raw: Raw<sys::signal_message>,
+}
+
+impl SignalMessage {
+    pub fn deserialize(
+        ctx: &Context,
+        data: &[u8],
+    ) -> Result<SignalMessage, Error> {
+        unsafe { SignalMessage::deserialize_with_raw_context(ctx.raw(), data) }
+    }
+
+    unsafe fn deserialize_with_raw_context(
+        ctx: *mut sys::signal_context,
+        data: &[u8],
+    ) -> Result<SignalMessage, Error> {
+        let mut raw = ptr::null_mut();
+        sys::signal_message_deserialize(
+            &mut raw,
+            data.as_ptr(),
+            data.len(),
+            ctx,
+        )
+        .into_result()?;
+
+        Ok(SignalMessage {
+            raw: Raw::from_ptr(raw),
+        })
+    }
+
+    /// Does this look like a message from a legacy (pre-v3) version of the
+    /// protocol, which `libsignal-protocol-c` can't decrypt?
+    pub fn is_legacy(data: &[u8]) -> bool {
+        unsafe { sys::signal_message_is_legacy(data.as_ptr(), data.len()) != 0 }
+    }
+
+    /// The bytes to send over the wire.
+    pub fn serialize(&self) -> &[u8] {
+        unsafe { super::serialized(self.raw.as_const_ptr()) }
+    }
+
+    pub fn message_version(&self) -> u8 {
+        unsafe {
+            sys::signal_message_get_message_version(self.raw.as_const_ptr())
+        }
+    }
+
+    /// The sender's current ratchet key.
+    pub fn sender_ratchet_key(&self) -> PublicKey {
+        unsafe {
+            let raw = sys::signal_message_get_sender_ratchet_key(
+                self.raw.as_const_ptr(),
+            );
+            assert!(!raw.is_null());
+            PublicKey {
+                raw: Raw::copied_from(raw),
+            }
+        }
+    }
+
+    /// The message's position in the sender's current chain.
+    pub fn counter(&self) -> u32 {
+        unsafe { sys::signal_message_get_counter(self.raw.as_const_ptr()) }
+    }
+
+    /// The encrypted message body.
+    pub fn body(&self) -> &[u8] {
+        unsafe {
+            // the buffer is owned by the message, so we mustn't free it
+            let buffer = sys::signal_message_get_body(self.raw.as_const_ptr());
+            assert!(!buffer.is_null());
+
+            std::slice::from_raw_parts(
+                sys::signal_buffer_data(buffer),
+                sys::signal_buffer_len(buffer),
+            )
+        }
+    }
+
+    /// Check the message's MAC, which covers both parties' identity keys as
+    /// well as the message itself.
+    pub fn verify_mac(
+        &self,
+        ctx: &Context,
+        sender_identity_key: &PublicKey,
+        receiver_identity_key: &PublicKey,
+        mac_key: &[u8],
+    ) -> Result<bool, Error> {
+        let result = unsafe {
+            sys::signal_message_verify_mac(
+                self.raw.as_ptr(),
+                sender_identity_key.raw.as_ptr(),
+                receiver_identity_key.raw.as_ptr(),
+                mac_key.as_ptr(),
+                mac_key.len(),
+                ctx.raw(),
+            )
+        };
+
+        match result {
+            1 => Ok(true),
+            0 => Ok(false),
+            other => match InternalError::from_error_code(other) {
+                Some(err) => Err(err.into()),
+                None => {
+                    Err(failure::format_err!("Unknown error code: {}", other))
+                },
+            },
+        }
+    }
+}
+
+impl_try_from_bytes!(
+    SignalMessage,
+    SignalMessage::deserialize_with_raw_context
+);
+
+impl Debug for SignalMessage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignalMessage")
+            .field("message_version", &self.message_version())
+            .field("sender_ratchet_key", &self.sender_ratchet_key())
+            .field("counter", &self.counter())
+            .field("body_len", &self.body().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "crypto-openssl")]
+    use crate::crypto::OpenSSLCrypto;
+    use crate::messages::test_utils;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn inspect_a_signal_message() {
+        let ctx = Context::default();
+        let ratchet_key = ctx.generate_key_pair().unwrap().public().unwrap();
+        let serialized =
+            test_utils::signal_message(&ratchet_key, 7, b"ciphertext");
+
+        let message = SignalMessage::try_from(&serialized[..]).unwrap();
+
+        assert_eq!(message.message_version(), 3);
+        assert_eq!(message.sender_ratchet_key(), ratchet_key);
+        assert_eq!(message.counter(), 7);
+        assert_eq!(message.body(), b"ciphertext");
+        assert_eq!(message.serialize(), &serialized[..]);
+        assert!(!SignalMessage::is_legacy(&serialized));
+        assert!(SignalMessage::is_legacy(&[0x11, 0x0a]));
+    }
+
+    #[test]
+    #[cfg(feature = "crypto-openssl")]
+    fn a_bad_mac_is_detected() {
+        let ctx = Context::new(OpenSSLCrypto).unwrap();
+        let key = ctx.generate_key_pair().unwrap().public().unwrap();
+        let serialized = test_utils::signal_message(&key, 7, b"ciphertext");
+        let message = SignalMessage::try_from(serialized).unwrap();
+
+        assert!(!message.verify_mac(&ctx, &key, &key, &[0; 32]).unwrap());
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        assert!(SignalMessage::try_from(&[0x33, 0xde, 0xad][..]).is_err());
+    }
+}
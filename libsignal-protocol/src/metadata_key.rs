@@ -0,0 +1,171 @@
+use crate::{
+    attachment::constant_time_eq, errors::InternalError, redact::Redacted,
+    Address, Context, StoreContext,
+};
+use failure::Error;
+use std::fmt::{self, Debug, Formatter};
+
+const KEY_LEN: usize = 32;
+const MAC_LEN: usize = 32;
+const HKDF_VERSION: i32 = 3;
+const INFO_PREFIX: &[u8] = b"libsignal-protocol-rs metadata mac v1\0";
+
+/// A HMAC-SHA256 key for authenticating out-of-band application metadata
+/// (typing indicators, read receipts, etc.).
+///
+/// Keys are derived from secret material both parties already share (usually
+/// the session, see [`MetadataKey::from_session()`]) using HKDF, with a label
+/// so each kind of metadata gets its own independent key.
+#[derive(Clone)]
+pub struct MetadataKey {
+    key: [u8; KEY_LEN],
+}
+
+impl MetadataKey {
+    /// The length of a MAC produced by [`MetadataKey::mac()`].
+    pub const MAC_LEN: usize = MAC_LEN;
+
+    /// Derive the key for a particular `label` from our session with
+    /// `address`.
+    ///
+    /// The key comes from the Diffie-Hellman agreement between the two
+    /// identity keys, salted with the session's
+    /// [`alice_base_key()`](crate::SessionState::alice_base_key), so it
+    /// doesn't change as the ratchet moves forward but is replaced along
+    /// with the session. Both sides get the same key once they agree on the
+    /// session, i.e. after the initiator's first message has been received.
+    ///
+    /// Fails with [`InternalError::NoSession`] if there is no session with
+    /// `address`.
+    pub fn from_session(
+        ctx: &Context,
+        store_ctx: &StoreContext,
+        address: &Address<'_>,
+        label: &str,
+    ) -> Result<MetadataKey, Error> {
+        let record = store_ctx.load_session(address)?;
+        if record.is_fresh() {
+            return Err(InternalError::NoSession.into());
+        }
+
+        let state = record.state();
+        let (remote_identity, alice_base_key) =
+            match (state.remote_identity_key(), state.alice_base_key()) {
+                (Some(remote), Some(base)) => (remote, base),
+                _ => return Err(InternalError::NoSession.into()),
+            };
+
+        let shared_secret = store_ctx
+            .identity_key_pair()?
+            .private_key()?
+            .calculate_agreement(&remote_identity)?;
+        let mut salt = Vec::new();
+        alice_base_key.serialize(&mut salt)?;
+
+        MetadataKey::derive_with_salt(ctx, &shared_secret.0, &salt, label)
+    }
+
+    /// Derive the key for a particular `label` from some other shared
+    /// secret.
+    pub fn derive(
+        ctx: &Context,
+        shared_secret: &[u8],
+        label: &str,
+    ) -> Result<MetadataKey, Error> {
+        MetadataKey::derive_with_salt(ctx, shared_secret, &[], label)
+    }
+
+    fn derive_with_salt(
+        ctx: &Context,
+        shared_secret: &[u8],
+        salt: &[u8],
+        label: &str,
+    ) -> Result<MetadataKey, Error> {
+        let mut info = Vec::with_capacity(INFO_PREFIX.len() + label.len());
+        info.extend_from_slice(INFO_PREFIX);
+        info.extend_from_slice(label.as_bytes());
+
+        let derived = ctx.create_hkdf(HKDF_VERSION)?.derive_secrets(
+            KEY_LEN,
+            shared_secret,
+            salt,
+            &info,
+        )?;
+
+        let mut key = [0; KEY_LEN];
+        key.copy_from_slice(&derived);
+
+        Ok(MetadataKey { key })
+    }
+
+    /// Calculate the MAC for some data.
+    pub fn mac(&self, ctx: &Context, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut hmac = ctx.crypto().hmac_sha256(&self.key)?;
+        hmac.update(data)?;
+        let mac = hmac.finalize()?;
+
+        if mac.len() < MAC_LEN {
+            return Err(InternalError::InvalidMAC.into());
+        }
+
+        Ok(mac[..MAC_LEN].to_vec())
+    }
+
+    /// Check a MAC previously calculated with [`MetadataKey::mac()`], failing
+    /// with [`InternalError::InvalidMAC`] if it doesn't match.
+    pub fn verify(
+        &self,
+        ctx: &Context,
+        data: &[u8],
+        mac: &[u8],
+    ) -> Result<(), Error> {
+        let ours = self.mac(ctx, data)?;
+
+        if constant_time_eq(&ours, mac) {
+            Ok(())
+        } else {
+            Err(InternalError::InvalidMAC.into())
+        }
+    }
+}
+
+impl Debug for MetadataKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetadataKey")
+            .field("key", &Redacted)
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "crypto-openssl"))]
+mod tests {
+    use super::*;
+    use crate::crypto::OpenSSLCrypto;
+
+    #[test]
+    fn verify_a_mac() {
+        let ctx = Context::new(OpenSSLCrypto).unwrap();
+        let key =
+            MetadataKey::derive(&ctx, b"shared secret", "typing").unwrap();
+
+        let mac = key.mac(&ctx, b"started").unwrap();
+
+        assert_eq!(mac.len(), MetadataKey::MAC_LEN);
+        key.verify(&ctx, b"started", &mac).unwrap();
+        assert!(key.verify(&ctx, b"stopped", &mac).is_err());
+    }
+
+    #[test]
+    fn labels_give_independent_keys() {
+        let ctx = Context::new(OpenSSLCrypto).unwrap();
+
+        let typing =
+            MetadataKey::derive(&ctx, b"shared secret", "typing").unwrap();
+        let receipts =
+            MetadataKey::derive(&ctx, b"shared secret", "receipts").unwrap();
+
+        let typing_mac = typing.mac(&ctx, b"data").unwrap();
+        let receipts_mac = receipts.mac(&ctx, b"data").unwrap();
+        assert!(!constant_time_eq(&typing_mac, &receipts_mac));
+    }
+}
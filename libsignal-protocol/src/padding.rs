@@ -0,0 +1,98 @@
+use crate::errors::InternalError;
+use failure::Error;
+
+/// The block size Signal clients pad messages to.
+const SIGNAL_BLOCK_SIZE: usize = 160;
+
+/// ISO 7816-4 style padding, which hides the exact length of a message by
+/// rounding it up to a multiple of the block size.
+///
+/// The message is followed by a single `0x80` byte and then as many zeroes
+/// as needed, so there is always at least one byte of padding.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Padding {
+    block_size: usize,
+}
+
+impl Padding {
+    /// # Panics
+    ///
+    /// If the `block_size` is zero.
+    pub fn new(block_size: usize) -> Padding {
+        assert!(block_size > 0, "The block size must be non-zero");
+
+        Padding { block_size }
+    }
+
+    pub fn block_size(self) -> usize { self.block_size }
+
+    /// Pad a message before it is encrypted.
+    pub fn pad(self, message: &[u8]) -> Vec<u8> {
+        let padded_len =
+            (message.len() / self.block_size + 1) * self.block_size;
+        let mut padded = Vec::with_capacity(padded_len);
+
+        padded.extend_from_slice(message);
+        padded.push(0x80);
+        padded.resize(padded_len, 0);
+
+        padded
+    }
+
+    /// Strip the padding from a decrypted message, failing with
+    /// [`InternalError::InvalidMessage`] if it isn't padded properly.
+    pub fn unpad(self, padded: &[u8]) -> Result<&[u8], Error> {
+        match padded.iter().rposition(|&b| b != 0) {
+            Some(end) if padded[end] == 0x80 => Ok(&padded[..end]),
+            _ => Err(InternalError::InvalidMessage.into()),
+        }
+    }
+}
+
+impl Default for Padding {
+    fn default() -> Padding { Padding::new(SIGNAL_BLOCK_SIZE) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_padded_to_a_whole_number_of_blocks() {
+        let padding = Padding::new(16);
+        let inputs = vec![(0, 16), (1, 16), (15, 16), (16, 32), (31, 32)];
+
+        for (len, should_be) in inputs {
+            let message = vec![0x42; len];
+
+            let padded = padding.pad(&message);
+
+            assert_eq!(padded.len(), should_be, "{} bytes", len);
+            assert_eq!(padding.unpad(&padded).unwrap(), &message[..]);
+        }
+    }
+
+    #[test]
+    fn trailing_zeroes_in_the_message_are_kept() {
+        let padding = Padding::default();
+        let message = b"Hello, World!\0\0";
+
+        let padded = padding.pad(message);
+
+        assert_eq!(padding.unpad(&padded).unwrap(), &message[..]);
+    }
+
+    #[test]
+    fn invalid_padding_is_rejected() {
+        let padding = Padding::default();
+
+        for padded in &[&[][..], &[0, 0, 0], &[0x42, 0x81, 0]] {
+            let err = padding.unpad(padded).unwrap_err();
+
+            assert_eq!(
+                err.downcast_ref::<InternalError>(),
+                Some(&InternalError::InvalidMessage)
+            );
+        }
+    }
+}
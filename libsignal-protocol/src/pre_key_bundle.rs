@@ -1,20 +1,84 @@
-use crate::{keys::PublicKey, raw_ptr::Raw};
+use crate::{
+    errors::{FromInternalErrorCode, InternalError},
+    ids::{DeviceId, PreKeyId, SignedPreKeyId},
+    keys::PublicKey,
+    raw_ptr::Raw,
+};
 use failure::Error;
-use std::{convert::TryInto, ptr};
+use std::{
+    fmt::{self, Debug, Display, Formatter},
+    ptr,
+};
 
+/// The reasons a [`PreKeyBundle`] may be rejected.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, failure_derive::Fail)]
+pub enum PreKeyBundleError {
+    /// No registration ID was provided.
+    MissingRegistrationId,
+    /// No device ID was provided.
+    MissingDeviceId,
+    /// No signed pre-key was provided.
+    MissingSignedPreKey,
+    /// No signature for the signed pre-key was provided.
+    MissingSignature,
+    /// No identity key was provided.
+    MissingIdentityKey,
+    /// The signature isn't the length of a curve25519 signature.
+    InvalidSignatureLength(usize),
+    /// The signed pre-key wasn't signed by the bundle's identity key.
+    InvalidSignature,
+}
+
+impl Display for PreKeyBundleError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            PreKeyBundleError::MissingRegistrationId => {
+                write!(f, "No registration ID was provided")
+            },
+            PreKeyBundleError::MissingDeviceId => {
+                write!(f, "No device ID was provided")
+            },
+            PreKeyBundleError::MissingSignedPreKey => {
+                write!(f, "No signed pre-key was provided")
+            },
+            PreKeyBundleError::MissingSignature => {
+                write!(f, "No signed pre-key signature was provided")
+            },
+            PreKeyBundleError::MissingIdentityKey => {
+                write!(f, "No identity key was provided")
+            },
+            PreKeyBundleError::InvalidSignatureLength(len) => write!(
+                f,
+                "Expected a {} byte signature but got {} bytes",
+                sys::CURVE_SIGNATURE_LEN,
+                len
+            ),
+            PreKeyBundleError::InvalidSignature => {
+                write!(f, "The signed pre-key signature is invalid")
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct PreKeyBundleBuilder {
     registration_id: Option<u32>,
-    device_id: Option<u32>,
-    pre_key_id: Option<u32>,
+    device_id: Option<DeviceId>,
+    pre_key_id: Option<PreKeyId>,
     pre_key_public: Option<PublicKey>,
-    signed_pre_key_id: Option<u32>,
+    signed_pre_key_id: Option<SignedPreKeyId>,
     signed_pre_key_public: Option<PublicKey>,
     signature: Option<Vec<u8>>,
     identity_key: Option<PublicKey>,
 }
 
 impl PreKeyBundleBuilder {
-    pub fn pre_key(mut self, id: u32, public_key: &PublicKey) -> Self {
+    /// Add a one-time pre-key.
+    ///
+    /// This is optional. Once a recipient has run out of one-time pre-keys
+    /// their bundles only contain a signed pre-key, and a session can still
+    /// be established from that.
+    pub fn pre_key(mut self, id: PreKeyId, public_key: &PublicKey) -> Self {
         self.pre_key_id = Some(id);
         self.pre_key_public = Some(public_key.clone());
 
@@ -23,7 +87,7 @@ impl PreKeyBundleBuilder {
 
     pub fn signed_pre_key(
         mut self,
-        id: u32,
+        id: SignedPreKeyId,
         signed_public_key: &PublicKey,
     ) -> Self {
         self.signed_pre_key_id = Some(id);
@@ -42,7 +106,7 @@ impl PreKeyBundleBuilder {
         self
     }
 
-    pub fn device_id(mut self, id: u32) -> Self {
+    pub fn device_id(mut self, id: DeviceId) -> Self {
         self.device_id = Some(id);
         self
     }
@@ -52,39 +116,57 @@ impl PreKeyBundleBuilder {
         self
     }
 
+    /// Assemble the [`PreKeyBundle`], failing with a [`PreKeyBundleError`]
+    /// if a component is missing or malformed.
     pub fn build(self) -> Result<PreKeyBundle, Error> {
-        if let PreKeyBundleBuilder {
-            registration_id: Some(registration_id),
-            device_id: Some(device_id),
-            pre_key_id: Some(pre_key_id),
-            pre_key_public: Some(pre_key_public),
-            signed_pre_key_id: Some(signed_pre_key_id),
-            signed_pre_key_public: Some(signed_pre_key_public),
-            signature: Some(signature),
-            identity_key: Some(identity_key),
-        } = self
-        {
-            unsafe {
-                let mut raw = ptr::null_mut();
-
-                sys::session_pre_key_bundle_create(
-                    &mut raw,
-                    registration_id,
-                    device_id.try_into().unwrap(),
-                    pre_key_id,
-                    pre_key_public.raw.as_ptr(),
-                    signed_pre_key_id,
-                    signed_pre_key_public.raw.as_ptr(),
-                    signature.as_ptr(),
-                    signature.len(),
-                    identity_key.raw.as_ptr(),
-                );
-                Ok(PreKeyBundle {
-                    raw: Raw::from_ptr(raw),
-                })
-            }
-        } else {
-            Err(failure::err_msg("Not all builder methods were executed"))
+        let registration_id = self
+            .registration_id
+            .ok_or(PreKeyBundleError::MissingRegistrationId)?;
+        let device_id =
+            self.device_id.ok_or(PreKeyBundleError::MissingDeviceId)?;
+        let (pre_key_id, pre_key_public) =
+            match (self.pre_key_id, self.pre_key_public.as_ref()) {
+                (Some(id), Some(public)) => (id.value(), public.raw.as_ptr()),
+                _ => (0, ptr::null_mut()),
+            };
+        let (signed_pre_key_id, signed_pre_key_public) =
+            match (self.signed_pre_key_id, self.signed_pre_key_public) {
+                (Some(id), Some(public)) => (id, public),
+                _ => return Err(PreKeyBundleError::MissingSignedPreKey.into()),
+            };
+        let signature =
+            self.signature.ok_or(PreKeyBundleError::MissingSignature)?;
+        let identity_key = self
+            .identity_key
+            .ok_or(PreKeyBundleError::MissingIdentityKey)?;
+
+        if signature.len() != sys::CURVE_SIGNATURE_LEN as usize {
+            return Err(PreKeyBundleError::InvalidSignatureLength(
+                signature.len(),
+            )
+            .into());
+        }
+
+        unsafe {
+            let mut raw = ptr::null_mut();
+
+            sys::session_pre_key_bundle_create(
+                &mut raw,
+                registration_id,
+                device_id.raw(),
+                pre_key_id,
+                pre_key_public,
+                signed_pre_key_id.value(),
+                signed_pre_key_public.raw.as_ptr(),
+                signature.as_ptr(),
+                signature.len(),
+                identity_key.raw.as_ptr(),
+            )
+            .into_result()?;
+
+            Ok(PreKeyBundle {
+                raw: Raw::from_ptr(raw),
+            })
         }
     }
 }
@@ -107,4 +189,583 @@ impl PreKeyBundle {
             identity_key: None,
         }
     }
+
+    pub fn registration_id(&self) -> u32 {
+        unsafe {
+            sys::session_pre_key_bundle_get_registration_id(
+                self.raw.as_const_ptr(),
+            )
+        }
+    }
+
+    pub fn device_id(&self) -> DeviceId {
+        unsafe {
+            DeviceId::from_raw(sys::session_pre_key_bundle_get_device_id(
+                self.raw.as_const_ptr(),
+            ))
+        }
+    }
+
+    /// The ID of the one-time pre-key, if the bundle has one.
+    pub fn pre_key_id(&self) -> Option<PreKeyId> {
+        self.pre_key().map(|_| unsafe {
+            PreKeyId::new(sys::session_pre_key_bundle_get_pre_key_id(
+                self.raw.as_const_ptr(),
+            ))
+        })
+    }
+
+    /// The one-time pre-key, if the bundle has one.
+    pub fn pre_key(&self) -> Option<PublicKey> {
+        unsafe {
+            let raw = sys::session_pre_key_bundle_get_pre_key(
+                self.raw.as_const_ptr(),
+            );
+
+            if raw.is_null() {
+                None
+            } else {
+                Some(PublicKey {
+                    raw: Raw::copied_from(raw),
+                })
+            }
+        }
+    }
+
+    pub fn signed_pre_key_id(&self) -> SignedPreKeyId {
+        unsafe {
+            SignedPreKeyId::new(
+                sys::session_pre_key_bundle_get_signed_pre_key_id(
+                    self.raw.as_const_ptr(),
+                ),
+            )
+        }
+    }
+
+    pub fn signed_pre_key(&self) -> PublicKey {
+        unsafe {
+            let raw = sys::session_pre_key_bundle_get_signed_pre_key(
+                self.raw.as_const_ptr(),
+            );
+            assert!(!raw.is_null());
+            PublicKey {
+                raw: Raw::copied_from(raw),
+            }
+        }
+    }
+
+    /// The signature over the signed pre-key's public key.
+    pub fn signed_pre_key_signature(&self) -> &[u8] {
+        unsafe {
+            // the buffer is owned by the bundle, so we mustn't free it
+            let buffer =
+                sys::session_pre_key_bundle_get_signed_pre_key_signature(
+                    self.raw.as_const_ptr(),
+                );
+
+            if buffer.is_null() {
+                &[]
+            } else {
+                std::slice::from_raw_parts(
+                    sys::signal_buffer_data(buffer),
+                    sys::signal_buffer_len(buffer),
+                )
+            }
+        }
+    }
+
+    /// A copy of this bundle with the one-time pre-key removed.
+    pub(crate) fn without_pre_key(&self) -> Result<PreKeyBundle, Error> {
+        PreKeyBundle::builder()
+            .registration_id(self.registration_id())
+            .device_id(self.device_id())
+            .signed_pre_key(self.signed_pre_key_id(), &self.signed_pre_key())
+            .signature(self.signed_pre_key_signature())
+            .identity_key(&self.identity_key())
+            .build()
+    }
+
+    /// Check the signed pre-key was signed by the bundle's identity key.
+    ///
+    /// This is done when the bundle is processed anyway, but doing it up
+    /// front lets a client reject a tampered bundle with a
+    /// [`PreKeyBundleError::InvalidSignature`] instead of a generic session
+    /// error.
+    pub fn verify(&self) -> Result<(), Error> {
+        let mut signed_pre_key = Vec::new();
+        self.signed_pre_key().serialize(&mut signed_pre_key)?;
+        let signature = self.signed_pre_key_signature();
+
+        let result = unsafe {
+            sys::curve_verify_signature(
+                self.identity_key().raw.as_const_ptr(),
+                signed_pre_key.as_ptr(),
+                signed_pre_key.len(),
+                signature.as_ptr(),
+                signature.len(),
+            )
+        };
+
+        match result {
+            1 => Ok(()),
+            0 => Err(PreKeyBundleError::InvalidSignature.into()),
+            // the library rejects signatures of the wrong length up front
+            sys::SG_ERR_INVAL => {
+                Err(PreKeyBundleError::InvalidSignature.into())
+            },
+            other => match InternalError::from_error_code(other) {
+                Some(err) => Err(err.into()),
+                None => {
+                    Err(failure::format_err!("Unknown error code: {}", other))
+                },
+            },
+        }
+    }
+
+    pub fn identity_key(&self) -> PublicKey {
+        unsafe {
+            let raw = sys::session_pre_key_bundle_get_identity_key(
+                self.raw.as_const_ptr(),
+            );
+            assert!(!raw.is_null());
+            PublicKey {
+                raw: Raw::copied_from(raw),
+            }
+        }
+    }
+}
+
+impl Debug for PreKeyBundle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PreKeyBundle")
+            .field("registration_id", &self.registration_id())
+            .field("device_id", &self.device_id())
+            .field("pre_key_id", &self.pre_key_id())
+            .field("signed_pre_key_id", &self.signed_pre_key_id())
+            .field("identity_key", &self.identity_key())
+            .finish()
+    }
+}
+
+#[cfg(feature = "serde-support")]
+mod serialization {
+    use super::{PreKeyBundle, PublicKey};
+    use crate::ids::{DeviceId, PreKeyId, SignedPreKeyId};
+    use failure::Error;
+    use serde::{
+        de::{self, Deserializer},
+        ser::{self, Serializer},
+        Deserialize, Serialize,
+    };
+    use std::convert::TryFrom;
+
+    /// The on-the-wire form of a [`PreKeyBundle`], with every key and the
+    /// signature encoded as base64.
+    #[derive(Serialize, Deserialize)]
+    struct SerializedBundle {
+        registration_id: u32,
+        device_id: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pre_key_id: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pre_key: Option<String>,
+        signed_pre_key_id: u32,
+        signed_pre_key: String,
+        signature: String,
+        identity_key: String,
+    }
+
+    fn encode_key<E: ser::Error>(key: &PublicKey) -> Result<String, E> {
+        let mut buffer = Vec::new();
+        key.serialize(&mut buffer).map_err(E::custom)?;
+        Ok(base64::encode(&buffer))
+    }
+
+    fn decode_bytes<E: de::Error>(encoded: &str) -> Result<Vec<u8>, E> {
+        base64::decode(encoded).map_err(E::custom)
+    }
+
+    fn decode_key<E: de::Error>(encoded: &str) -> Result<PublicKey, E> {
+        let bytes = decode_bytes(encoded)?;
+        PublicKey::try_from(bytes).map_err(E::custom)
+    }
+
+    /// A single entry in the `devices` array returned by a Signal server's
+    /// `/v2/keys/{name}/{device}` endpoint.
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ServerDevice {
+        device_id: u32,
+        registration_id: u32,
+        signed_pre_key: ServerSignedPreKey,
+        pre_key: Option<ServerPreKey>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ServerSignedPreKey {
+        key_id: u32,
+        public_key: String,
+        signature: String,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ServerPreKey {
+        key_id: u32,
+        public_key: String,
+    }
+
+    /// The type byte `libsignal-protocol-c` puts in front of curve25519 keys.
+    const DJB_TYPE: u8 = 0x05;
+    const DJB_KEY_LEN: usize = 32;
+
+    /// Servers aren't consistent about including the key type byte, so
+    /// bare 32-byte curve25519 keys get the prefix added back.
+    fn decode_server_key(encoded: &str) -> Result<PublicKey, Error> {
+        let mut bytes = base64::decode(encoded)?;
+
+        if bytes.len() == DJB_KEY_LEN {
+            bytes.insert(0, DJB_TYPE);
+        }
+
+        PublicKey::try_from(bytes)
+    }
+
+    impl PreKeyBundle {
+        /// Create a [`PreKeyBundle`] from the JSON for one device in a
+        /// Signal server's pre-key response.
+        ///
+        /// The identity key is shared by all of a user's devices, so the
+        /// server sends it once at the top of the response rather than in
+        /// each device entry. Keys may be base64 with or without padding and
+        /// with or without the leading key type byte.
+        ///
+        /// The resulting bundle has already been checked with
+        /// [`PreKeyBundle::verify()`].
+        pub fn from_server_json(
+            identity_key: &str,
+            device: &str,
+        ) -> Result<PreKeyBundle, Error> {
+            let device: ServerDevice = serde_json::from_str(device)?;
+
+            let mut builder = PreKeyBundle::builder()
+                .registration_id(device.registration_id)
+                .device_id(DeviceId::new(device.device_id)?)
+                .signed_pre_key(
+                    SignedPreKeyId::new(device.signed_pre_key.key_id),
+                    &decode_server_key(&device.signed_pre_key.public_key)?,
+                )
+                .signature(&base64::decode(&device.signed_pre_key.signature)?)
+                .identity_key(&decode_server_key(identity_key)?);
+
+            if let Some(pre_key) = device.pre_key {
+                builder = builder.pre_key(
+                    PreKeyId::new(pre_key.key_id),
+                    &decode_server_key(&pre_key.public_key)?,
+                );
+            }
+
+            let bundle = builder.build()?;
+            bundle.verify()?;
+
+            Ok(bundle)
+        }
+    }
+
+    impl Serialize for PreKeyBundle {
+        fn serialize<S: Serializer>(
+            &self,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            SerializedBundle {
+                registration_id: self.registration_id(),
+                device_id: self.device_id().value(),
+                pre_key_id: self.pre_key_id().map(PreKeyId::value),
+                pre_key: match self.pre_key() {
+                    Some(key) => Some(encode_key(&key)?),
+                    None => None,
+                },
+                signed_pre_key_id: self.signed_pre_key_id().value(),
+                signed_pre_key: encode_key(&self.signed_pre_key())?,
+                signature: base64::encode(self.signed_pre_key_signature()),
+                identity_key: encode_key(&self.identity_key())?,
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for PreKeyBundle {
+        fn deserialize<D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<PreKeyBundle, D::Error> {
+            let bundle = SerializedBundle::deserialize(deserializer)?;
+
+            let mut builder = PreKeyBundle::builder()
+                .registration_id(bundle.registration_id)
+                .device_id(
+                    DeviceId::new(bundle.device_id)
+                        .map_err(de::Error::custom)?,
+                )
+                .signed_pre_key(
+                    SignedPreKeyId::new(bundle.signed_pre_key_id),
+                    &decode_key(&bundle.signed_pre_key)?,
+                )
+                .signature(&decode_bytes::<D::Error>(&bundle.signature)?)
+                .identity_key(&decode_key(&bundle.identity_key)?);
+
+            match (bundle.pre_key_id, bundle.pre_key) {
+                (Some(id), Some(key)) => {
+                    builder =
+                        builder.pre_key(PreKeyId::new(id), &decode_key(&key)?);
+                },
+                (None, None) => {},
+                _ => return Err(de::Error::custom(
+                    "The pre-key ID and public key must be provided together",
+                )),
+            }
+
+            builder.build().map_err(de::Error::custom)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Context;
+
+    fn device(id: u32) -> DeviceId { DeviceId::new(id).unwrap() }
+
+    #[test]
+    fn inspect_a_bundle() {
+        let ctx = Context::default();
+        let pre_key = ctx.generate_key_pair().unwrap().public().unwrap();
+        let signed_pre_key = ctx.generate_key_pair().unwrap().public().unwrap();
+        let identity_key = ctx.generate_key_pair().unwrap().public().unwrap();
+        let signature = [0xab; 64];
+
+        let bundle = PreKeyBundle::builder()
+            .registration_id(42)
+            .device_id(device(7))
+            .pre_key(PreKeyId::new(31337), &pre_key)
+            .signed_pre_key(SignedPreKeyId::new(22), &signed_pre_key)
+            .signature(&signature)
+            .identity_key(&identity_key)
+            .build()
+            .unwrap();
+
+        assert_eq!(bundle.registration_id(), 42);
+        assert_eq!(bundle.device_id(), device(7));
+        assert_eq!(bundle.pre_key_id(), Some(PreKeyId::new(31337)));
+        assert_eq!(bundle.pre_key(), Some(pre_key));
+        assert_eq!(bundle.signed_pre_key_id(), SignedPreKeyId::new(22));
+        assert_eq!(bundle.signed_pre_key(), signed_pre_key);
+        assert_eq!(bundle.signed_pre_key_signature(), &signature[..]);
+        assert_eq!(bundle.identity_key(), identity_key);
+    }
+
+    fn signed_bundle(ctx: &Context, signature: Option<&[u8]>) -> PreKeyBundle {
+        let identity = ctx.generate_key_pair().unwrap();
+        let signed_pre_key = ctx.generate_key_pair().unwrap().public().unwrap();
+
+        let mut serialized = Vec::new();
+        signed_pre_key.serialize(&mut serialized).unwrap();
+        let real_signature = ctx
+            .calculate_signature(&identity.private().unwrap(), &serialized)
+            .unwrap();
+        let signature = signature.unwrap_or(real_signature.as_slice());
+
+        PreKeyBundle::builder()
+            .registration_id(42)
+            .device_id(device(1))
+            .pre_key(
+                PreKeyId::new(1),
+                &ctx.generate_key_pair().unwrap().public().unwrap(),
+            )
+            .signed_pre_key(SignedPreKeyId::new(2), &signed_pre_key)
+            .signature(signature)
+            .identity_key(&identity.public().unwrap())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn bundles_without_a_one_time_pre_key() {
+        let ctx = Context::default();
+        let key = ctx.generate_key_pair().unwrap().public().unwrap();
+
+        let bundle = PreKeyBundle::builder()
+            .registration_id(42)
+            .device_id(device(1))
+            .signed_pre_key(SignedPreKeyId::new(2), &key)
+            .signature(&[0; 64])
+            .identity_key(&key)
+            .build()
+            .unwrap();
+
+        assert_eq!(bundle.pre_key_id(), None);
+        assert!(bundle.pre_key().is_none());
+        assert_eq!(bundle.signed_pre_key_id(), SignedPreKeyId::new(2));
+    }
+
+    #[test]
+    fn correctly_signed_bundles_verify() {
+        let ctx = Context::default();
+        let bundle = signed_bundle(&ctx, None);
+
+        bundle.verify().unwrap();
+    }
+
+    #[test]
+    fn tampered_signatures_are_rejected() {
+        let ctx = Context::default();
+
+        let bundle = signed_bundle(&ctx, Some(&[0xab; 64]));
+
+        let err = bundle.verify().unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<PreKeyBundleError>(),
+            Some(&PreKeyBundleError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn builder_reports_what_is_wrong() {
+        let ctx = Context::default();
+        let key = ctx.generate_key_pair().unwrap().public().unwrap();
+        let complete = || {
+            PreKeyBundle::builder()
+                .registration_id(42)
+                .device_id(device(1))
+                .pre_key(PreKeyId::new(1), &key)
+                .signed_pre_key(SignedPreKeyId::new(2), &key)
+                .signature(&[0; 64])
+                .identity_key(&key)
+        };
+        let error_of = |builder: PreKeyBundleBuilder| {
+            builder
+                .build()
+                .err()
+                .unwrap()
+                .downcast::<PreKeyBundleError>()
+                .unwrap()
+        };
+
+        let mut missing_identity_key = complete();
+        missing_identity_key.identity_key = None;
+        assert_eq!(
+            error_of(missing_identity_key),
+            PreKeyBundleError::MissingIdentityKey
+        );
+
+        let mut missing_signed_pre_key = complete();
+        missing_signed_pre_key.signed_pre_key_public = None;
+        assert_eq!(
+            error_of(missing_signed_pre_key),
+            PreKeyBundleError::MissingSignedPreKey
+        );
+
+        assert_eq!(
+            error_of(complete().signature(&[0; 12])),
+            PreKeyBundleError::InvalidSignatureLength(12)
+        );
+        assert!(complete().build().is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "serde-support")]
+    fn bundles_round_trip_through_json() {
+        let ctx = Context::default();
+        let bundle = signed_bundle(&ctx, None);
+
+        let json = serde_json::to_string(&bundle).unwrap();
+        let got: PreKeyBundle = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(got.registration_id(), bundle.registration_id());
+        assert_eq!(got.device_id(), bundle.device_id());
+        assert_eq!(got.pre_key_id(), bundle.pre_key_id());
+        assert_eq!(got.pre_key(), bundle.pre_key());
+        assert_eq!(got.signed_pre_key_id(), bundle.signed_pre_key_id());
+        assert_eq!(got.signed_pre_key(), bundle.signed_pre_key());
+        assert_eq!(
+            got.signed_pre_key_signature(),
+            bundle.signed_pre_key_signature()
+        );
+        assert_eq!(got.identity_key(), bundle.identity_key());
+    }
+
+    #[test]
+    #[cfg(feature = "serde-support")]
+    fn bundles_from_a_signal_server() {
+        let ctx = Context::default();
+        let bundle = signed_bundle(&ctx, None);
+        let encode = |key: PublicKey| {
+            let mut buffer = Vec::new();
+            key.serialize(&mut buffer).unwrap();
+            // strip the key type byte and the padding, like some servers do
+            base64::encode(&buffer[1..])
+                .trim_end_matches('=')
+                .to_string()
+        };
+        let device = serde_json::json!({
+            "deviceId": 1,
+            "registrationId": 42,
+            "signedPreKey": {
+                "keyId": 2,
+                "publicKey": encode(bundle.signed_pre_key()),
+                "signature": base64::encode(bundle.signed_pre_key_signature()),
+            },
+            "preKey": {
+                "keyId": 1,
+                "publicKey": encode(bundle.pre_key().unwrap()),
+            },
+        });
+
+        let got = PreKeyBundle::from_server_json(
+            &encode(bundle.identity_key()),
+            &device.to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(got.registration_id(), 42);
+        assert_eq!(got.device_id(), DeviceId::new(1).unwrap());
+        assert_eq!(got.pre_key(), bundle.pre_key());
+        assert_eq!(got.signed_pre_key(), bundle.signed_pre_key());
+        assert_eq!(got.identity_key(), bundle.identity_key());
+    }
+
+    #[test]
+    #[cfg(feature = "serde-support")]
+    fn server_devices_without_a_pre_key() {
+        let ctx = Context::default();
+        let bundle = signed_bundle(&ctx, None);
+        let encode = |key: PublicKey| {
+            let mut buffer = Vec::new();
+            key.serialize(&mut buffer).unwrap();
+            base64::encode(&buffer)
+        };
+        let device = serde_json::json!({
+            "deviceId": 1,
+            "registrationId": 42,
+            "signedPreKey": {
+                "keyId": 2,
+                "publicKey": encode(bundle.signed_pre_key()),
+                "signature": base64::encode(bundle.signed_pre_key_signature()),
+            },
+        });
+
+        let got = PreKeyBundle::from_server_json(
+            &encode(bundle.identity_key()),
+            &device.to_string(),
+        )
+        .unwrap();
+        assert!(got.pre_key().is_none());
+
+        let json = serde_json::to_string(&got).unwrap();
+        assert!(!json.contains("pre_key_id"));
+        let round_tripped: PreKeyBundle = serde_json::from_str(&json).unwrap();
+        assert_eq!(round_tripped.pre_key_id(), None);
+    }
 }
@@ -0,0 +1,186 @@
+use crate::{errors::InternalError, ids::PreKeyId, pre_key_store::PreKeyStore};
+use std::{
+    cell::Cell,
+    fmt::{self, Debug, Formatter},
+    io::{self, Write},
+    rc::Rc,
+};
+
+/// A [`PreKeyStore`] wrapper which keeps track of how many one-time pre-keys
+/// are left and raises the alarm when the pool is running low.
+///
+/// `libsignal-protocol-c` removes a one-time pre-key from the store as soon as
+/// a `PreKeySignalMessage` using it has been processed, so every successful
+/// removal is counted as a consumed key. Once the number of remaining keys
+/// drops below `threshold` the `on_low` callback is invoked (after every
+/// consumption) with the number of keys left, giving the application a chance
+/// to generate and upload more. A burst of consumptions is also a good
+/// indication someone is trying to drain the pool.
+///
+/// The guard is a cheap handle around shared state, so one clone can be given
+/// to [`Context::new_store_context()`] while the application keeps another for
+/// storing freshly generated keys and checking [`remaining()`].
+///
+/// [`Context::new_store_context()`]: crate::Context::new_store_context
+/// [`remaining()`]: PreKeyExhaustionGuard::remaining
+pub struct PreKeyExhaustionGuard<P> {
+    inner: Rc<Inner<P>>,
+}
+
+impl<P: PreKeyStore> PreKeyExhaustionGuard<P> {
+    /// Wrap a [`PreKeyStore`] which currently contains `remaining` one-time
+    /// pre-keys.
+    pub fn new<F>(
+        store: P,
+        remaining: usize,
+        threshold: usize,
+        on_low: F,
+    ) -> PreKeyExhaustionGuard<P>
+    where
+        F: Fn(usize) + 'static,
+    {
+        PreKeyExhaustionGuard {
+            inner: Rc::new(Inner {
+                store,
+                remaining: Cell::new(remaining),
+                threshold,
+                on_low: Box::new(on_low),
+            }),
+        }
+    }
+
+    /// How many one-time pre-keys are left?
+    pub fn remaining(&self) -> usize { self.inner.remaining.get() }
+
+    /// Is the number of remaining pre-keys below the threshold?
+    pub fn is_low(&self) -> bool { self.remaining() < self.inner.threshold }
+
+    /// Get a reference to the underlying store.
+    pub fn inner(&self) -> &P { &self.inner.store }
+}
+
+impl<P: PreKeyStore> PreKeyStore for PreKeyExhaustionGuard<P> {
+    fn load(&self, id: PreKeyId, writer: &mut dyn Write) -> io::Result<()> {
+        self.inner.store.load(id, writer)
+    }
+
+    fn store(&self, id: PreKeyId, body: &[u8]) -> Result<(), InternalError> {
+        let is_new = !self.inner.store.contains(id);
+        self.inner.store.store(id, body)?;
+
+        if is_new {
+            let remaining = &self.inner.remaining;
+            remaining.set(remaining.get().saturating_add(1));
+        }
+
+        Ok(())
+    }
+
+    fn contains(&self, id: PreKeyId) -> bool { self.inner.store.contains(id) }
+
+    fn remove(&self, id: PreKeyId) -> Result<(), InternalError> {
+        let existed = self.inner.store.contains(id);
+        self.inner.store.remove(id)?;
+
+        if existed {
+            let remaining = &self.inner.remaining;
+            remaining.set(remaining.get().saturating_sub(1));
+
+            if self.is_low() {
+                (self.inner.on_low)(remaining.get());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<P> Clone for PreKeyExhaustionGuard<P> {
+    fn clone(&self) -> PreKeyExhaustionGuard<P> {
+        PreKeyExhaustionGuard {
+            inner: Rc::clone(&self.inner),
+        }
+    }
+}
+
+impl<P> Debug for PreKeyExhaustionGuard<P> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PreKeyExhaustionGuard")
+            .field("remaining", &self.inner.remaining.get())
+            .field("threshold", &self.inner.threshold)
+            .finish()
+    }
+}
+
+struct Inner<P> {
+    store: P,
+    remaining: Cell<usize>,
+    threshold: usize,
+    on_low: Box<dyn Fn(usize)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, collections::HashMap};
+
+    #[derive(Default)]
+    struct InMemoryPreKeyStore(RefCell<HashMap<PreKeyId, Vec<u8>>>);
+
+    impl PreKeyStore for InMemoryPreKeyStore {
+        fn load(&self, id: PreKeyId, writer: &mut dyn Write) -> io::Result<()> {
+            match self.0.borrow().get(&id) {
+                Some(body) => writer.write_all(body),
+                None => Err(io::ErrorKind::NotFound.into()),
+            }
+        }
+
+        fn store(
+            &self,
+            id: PreKeyId,
+            body: &[u8],
+        ) -> Result<(), InternalError> {
+            self.0.borrow_mut().insert(id, body.to_vec());
+            Ok(())
+        }
+
+        fn contains(&self, id: PreKeyId) -> bool {
+            self.0.borrow().contains_key(&id)
+        }
+
+        fn remove(&self, id: PreKeyId) -> Result<(), InternalError> {
+            self.0.borrow_mut().remove(&id);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn callback_fires_once_the_pool_runs_low() {
+        let alerts = Rc::new(RefCell::new(Vec::new()));
+        let alerts_2 = Rc::clone(&alerts);
+        let guard = PreKeyExhaustionGuard::new(
+            InMemoryPreKeyStore::default(),
+            0,
+            2,
+            move |remaining| alerts_2.borrow_mut().push(remaining),
+        );
+
+        for id in 1..=3 {
+            guard.store(PreKeyId::new(id), b"pre-key").unwrap();
+        }
+        // overwriting an existing key doesn't add to the pool
+        guard.store(PreKeyId::new(1), b"pre-key").unwrap();
+        assert_eq!(guard.remaining(), 3);
+
+        guard.remove(PreKeyId::new(1)).unwrap();
+        assert!(alerts.borrow().is_empty());
+
+        guard.remove(PreKeyId::new(2)).unwrap();
+        // removing an unknown key isn't a consumption
+        guard.remove(PreKeyId::new(42)).unwrap();
+        guard.remove(PreKeyId::new(3)).unwrap();
+
+        assert_eq!(guard.remaining(), 0);
+        assert_eq!(*alerts.borrow(), vec![1, 0]);
+    }
+}
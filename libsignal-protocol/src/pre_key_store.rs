@@ -1,14 +1,14 @@
-use crate::{buffer::Buffer, errors::InternalError};
+use crate::{buffer::Buffer, errors::InternalError, ids::PreKeyId};
 use std::{
     io::{self, Write},
     os::raw::{c_int, c_void},
 };
 
 pub trait PreKeyStore {
-    fn load(&self, id: u32, writer: &mut dyn Write) -> io::Result<()>;
-    fn store(&self, id: u32, body: &[u8]) -> Result<(), InternalError>;
-    fn contains(&self, id: u32) -> bool;
-    fn remove(&self, id: u32) -> Result<(), InternalError>;
+    fn load(&self, id: PreKeyId, writer: &mut dyn Write) -> io::Result<()>;
+    fn store(&self, id: PreKeyId, body: &[u8]) -> Result<(), InternalError>;
+    fn contains(&self, id: PreKeyId) -> bool;
+    fn remove(&self, id: PreKeyId) -> Result<(), InternalError>;
 }
 
 pub(crate) fn new_vtable<P: PreKeyStore + 'static>(
@@ -38,7 +38,7 @@ unsafe extern "C" fn load_pre_key(
     let user_data = &*(user_data as *const State);
     let mut buffer = Buffer::new();
 
-    match user_data.0.load(pre_key_id, &mut buffer) {
+    match user_data.0.load(PreKeyId::new(pre_key_id), &mut buffer) {
         Ok(_) => {
             *record = buffer.into_raw();
             sys::SG_SUCCESS as c_int
@@ -58,7 +58,7 @@ unsafe extern "C" fn store_pre_key(
     let user_data = &*(user_data as *const State);
     let data = std::slice::from_raw_parts(record, record_len);
 
-    match user_data.0.store(pre_key_id, data) {
+    match user_data.0.store(PreKeyId::new(pre_key_id), data) {
         Ok(_) => sys::SG_SUCCESS as c_int,
         Err(e) => e.code(),
     }
@@ -71,7 +71,7 @@ unsafe extern "C" fn contains_pre_key(
     assert!(!user_data.is_null());
     let user_data = &*(user_data as *const State);
 
-    user_data.0.contains(pre_key_id) as c_int
+    user_data.0.contains(PreKeyId::new(pre_key_id)) as c_int
 }
 
 unsafe extern "C" fn remove_pre_key(
@@ -81,7 +81,7 @@ unsafe extern "C" fn remove_pre_key(
     assert!(!user_data.is_null());
     let user_data = &*(user_data as *const State);
 
-    match user_data.0.remove(pre_key_id) {
+    match user_data.0.remove(PreKeyId::new(pre_key_id)) {
         Ok(_) => sys::SG_SUCCESS as c_int,
         Err(e) => e.code(),
     }
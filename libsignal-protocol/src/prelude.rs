@@ -0,0 +1,17 @@
+//! The types and traits almost every user of this crate needs.
+//!
+//! ```rust
+//! use libsignal_protocol::prelude::*;
+//! ```
+
+pub use crate::{
+    crypto::Crypto,
+    keys::{
+        IdentityKeyPair, KeyPair, PreKey, PrivateKey, PublicKey,
+        SessionSignedPreKey,
+    },
+    messages::{CiphertextMessage, PreKeySignalMessage, SignalMessage},
+    Address, Buffer, Context, DeviceId, IdentityKeyStore, InternalError,
+    PreKeyBundle, PreKeyId, PreKeyStore, SessionBuilder, SessionCipher,
+    SessionStore, SignedPreKeyId, SignedPreKeyStore, StoreContext, Timestamp,
+};
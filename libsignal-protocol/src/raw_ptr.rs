@@ -58,4 +58,10 @@ impl_signal_type_base! {
     sys::ratchet_identity_key_pair, sys::session_signed_pre_key,
     sys::ec_public_key, sys::ec_private_key, sys::session_pre_key,
     sys::ec_key_pair, sys::session_pre_key_bundle, sys::hkdf_context,
+    sys::signal_message, sys::pre_key_signal_message, sys::sender_key_message,
+    sys::sender_key_distribution_message, sys::fingerprint,
+    sys::displayable_fingerprint, sys::scannable_fingerprint,
+    sys::device_consistency_commitment, sys::device_consistency_message,
+    sys::device_consistency_signature, sys::session_record,
+    sys::session_state,
 }
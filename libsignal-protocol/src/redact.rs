@@ -0,0 +1,62 @@
+//! Helpers for writing [`Debug`] impls which never leak secrets.
+
+use std::fmt::{self, Debug, Formatter};
+
+/// The number of bytes shown by [`ShortHex`].
+const SHORT_HEX_LEN: usize = 4;
+
+/// Stands in for a secret field.
+pub(crate) struct Redacted;
+
+impl Debug for Redacted {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+/// Shows the first few bytes of some (non-secret) data as hex, enough to
+/// tell two keys apart in a log.
+pub(crate) struct ShortHex<'a>(pub(crate) &'a [u8]);
+
+impl<'a> Debug for ShortHex<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for byte in self.0.iter().take(SHORT_HEX_LEN) {
+            write!(f, "{:02x}", byte)?;
+        }
+
+        if self.0.len() > SHORT_HEX_LEN {
+            f.write_str("...")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AttachmentKeys, Context};
+
+    #[test]
+    fn short_hex_only_shows_a_prefix() {
+        assert_eq!(format!("{:?}", ShortHex(&[0xde, 0xad])), "dead");
+        assert_eq!(
+            format!("{:?}", ShortHex(&[0xde, 0xad, 0xbe, 0xef, 0x01])),
+            "deadbeef..."
+        );
+    }
+
+    #[test]
+    fn secrets_are_redacted() {
+        let ctx = Context::default();
+        let key_pair = ctx.generate_key_pair().unwrap();
+        let keys = AttachmentKeys::from_bytes(&[0xab; 64]).unwrap();
+
+        assert_eq!(
+            format!("{:?}", key_pair.private().unwrap()),
+            "PrivateKey(<redacted>)"
+        );
+        assert!(format!("{:?}", key_pair).contains("private: <redacted>"));
+        assert!(!format!("{:?}", keys).contains("171"));
+    }
+}
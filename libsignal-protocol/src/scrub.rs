@@ -0,0 +1,18 @@
+use std::{
+    ptr,
+    sync::atomic::{self, Ordering},
+};
+
+/// Overwrites its contents with zeroes when dropped, for buffers holding
+/// key material.
+pub(crate) struct Scrubbed<T: AsMut<[u8]>>(pub(crate) T);
+
+impl<T: AsMut<[u8]>> Drop for Scrubbed<T> {
+    fn drop(&mut self) {
+        for byte in self.0.as_mut() {
+            // volatile so the writes can't be optimised away
+            unsafe { ptr::write_volatile(byte, 0) };
+        }
+        atomic::compiler_fence(Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,123 @@
+//! Interop with the [`secrecy`] crate, so key material can cross the crate
+//! boundary without being unwrapped into a plain `Vec<u8>`.
+
+use crate::{
+    errors::FromInternalErrorCode, keys::PrivateKey, AttachmentKeys,
+    BackupKeys, Context, HMACBasedKeyDerivationFunction,
+};
+use failure::Error;
+use secrecy::{ExposeSecret, SecretVec};
+use std::ptr;
+
+impl PrivateKey {
+    /// Serialize the private key, the same bytes [`PrivateKey::decode_point`]
+    /// accepts.
+    ///
+    /// The intermediate buffer allocated by `libsignal-protocol-c` is zeroed
+    /// before being freed.
+    pub fn serialize_secret(&self) -> Result<SecretVec<u8>, Error> {
+        unsafe {
+            let mut raw = ptr::null_mut();
+            sys::ec_private_key_serialize(&mut raw, self.raw.as_const_ptr())
+                .into_result()?;
+            assert!(!raw.is_null());
+
+            let data = std::slice::from_raw_parts(
+                sys::signal_buffer_data(raw),
+                sys::signal_buffer_len(raw),
+            )
+            .to_vec();
+            sys::signal_buffer_bzero_free(raw);
+
+            Ok(SecretVec::new(data))
+        }
+    }
+
+    /// Decode a private key previously serialized with
+    /// [`PrivateKey::serialize_secret`].
+    pub fn from_secret(
+        ctx: &Context,
+        secret: &SecretVec<u8>,
+    ) -> Result<PrivateKey, Error> {
+        PrivateKey::decode_point(ctx, secret.expose_secret())
+    }
+}
+
+impl HMACBasedKeyDerivationFunction {
+    /// The same as [`HMACBasedKeyDerivationFunction::derive_secrets`], except
+    /// the input key material and derived secret are kept in a
+    /// [`SecretVec`].
+    pub fn derive_secret(
+        &self,
+        secret_length: usize,
+        input_key_material: &SecretVec<u8>,
+        salt: &[u8],
+        info: &[u8],
+    ) -> Result<SecretVec<u8>, Error> {
+        self.derive_secrets(
+            secret_length,
+            input_key_material.expose_secret(),
+            salt,
+            info,
+        )
+        .map(SecretVec::new)
+    }
+}
+
+impl AttachmentKeys {
+    /// The same as [`AttachmentKeys::to_bytes`], wrapped in a [`SecretVec`].
+    pub fn to_secret(&self) -> SecretVec<u8> { SecretVec::new(self.to_bytes()) }
+}
+
+impl BackupKeys {
+    /// Derive the backup keys from a master secret held in a [`SecretVec`].
+    pub fn derive_from_secret(
+        ctx: &Context,
+        master_secret: &SecretVec<u8>,
+    ) -> Result<BackupKeys, Error> {
+        BackupKeys::derive(ctx, master_secret.expose_secret())
+    }
+
+    /// Encrypt a backup whose contents are held in a [`SecretVec`].
+    pub fn encrypt_secret(
+        &self,
+        ctx: &Context,
+        plaintext: &SecretVec<u8>,
+    ) -> Result<Vec<u8>, Error> {
+        self.encrypt(ctx, plaintext.expose_secret())
+    }
+
+    /// The same as [`BackupKeys::decrypt`], but the decrypted backup is
+    /// returned as a [`SecretVec`].
+    pub fn decrypt_secret(
+        &self,
+        ctx: &Context,
+        encrypted: &[u8],
+    ) -> Result<SecretVec<u8>, Error> {
+        self.decrypt(ctx, encrypted).map(SecretVec::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn private_keys_round_trip_through_a_secret() {
+        let ctx = Context::default();
+        let private = ctx.generate_key_pair().unwrap().private().unwrap();
+
+        let secret = private.serialize_secret().unwrap();
+        let got = PrivateKey::from_secret(&ctx, &secret).unwrap();
+
+        assert_eq!(got, private);
+    }
+
+    #[test]
+    fn master_secrets_are_still_validated() {
+        let ctx = Context::default();
+        let secret = SecretVec::new(vec![0; 16]);
+
+        assert!(BackupKeys::derive_from_secret(&ctx, &secret).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use crate::address::{Address, OwnedAddress};
+use std::{
+    fmt::{self, Debug, Formatter},
+    hash::{Hash, Hasher},
+    os::raw::c_char,
+};
+
+/// Identifies a sender key: the group it belongs to and the group member
+/// who sends messages with it.
+///
+/// Unlike [`Address`], this keeps its own copy of the group ID and the
+/// sender's name, so it can be stored or handed to a [`GroupCipher`]
+/// without borrowing anything.
+///
+/// [`GroupCipher`]: crate::GroupCipher
+pub struct SenderKeyName {
+    group_id: Box<[u8]>,
+    sender: OwnedAddress,
+    // `group_cipher` holds onto the pointer it was created with, so the raw
+    // struct is boxed to keep it at a stable location
+    raw: Box<sys::signal_protocol_sender_key_name>,
+}
+
+impl SenderKeyName {
+    pub fn new(group_id: &[u8], sender: &Address<'_>) -> SenderKeyName {
+        let group_id: Box<[u8]> = group_id.into();
+        let sender = OwnedAddress::new(sender);
+        let raw = Box::new(sys::signal_protocol_sender_key_name {
+            group_id: group_id.as_ptr() as *const c_char,
+            group_id_len: group_id.len(),
+            sender: unsafe { *sender.raw() },
+        });
+
+        SenderKeyName {
+            group_id,
+            sender,
+            raw,
+        }
+    }
+
+    /// Copy a sender key name passed to us by `libsignal-protocol-c`.
+    pub(crate) unsafe fn from_raw(
+        raw: *const sys::signal_protocol_sender_key_name,
+    ) -> SenderKeyName {
+        assert!(!raw.is_null());
+        // a null pointer isn't a valid empty slice
+        let group_id = if (*raw).group_id.is_null() {
+            &[]
+        } else {
+            std::slice::from_raw_parts(
+                (*raw).group_id as *const u8,
+                (*raw).group_id_len,
+            )
+        };
+
+        SenderKeyName::new(group_id, &Address::from_raw(&(*raw).sender))
+    }
+
+    pub fn group_id(&self) -> &[u8] { &self.group_id }
+
+    pub fn sender(&self) -> Address<'_> { self.sender.as_address() }
+
+    pub(crate) fn raw(&self) -> *const sys::signal_protocol_sender_key_name {
+        &*self.raw
+    }
+}
+
+impl Clone for SenderKeyName {
+    fn clone(&self) -> SenderKeyName {
+        SenderKeyName::new(self.group_id(), &self.sender())
+    }
+}
+
+impl PartialEq for SenderKeyName {
+    fn eq(&self, other: &SenderKeyName) -> bool {
+        let (sender, other_sender) = (self.sender(), other.sender());
+
+        self.group_id() == other.group_id()
+            && sender.bytes() == other_sender.bytes()
+            && sender.device_id() == other_sender.device_id()
+    }
+}
+
+impl Eq for SenderKeyName {}
+
+impl Hash for SenderKeyName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let sender = self.sender();
+
+        self.group_id().hash(state);
+        sender.bytes().hash(state);
+        sender.device_id().hash(state);
+    }
+}
+
+impl Debug for SenderKeyName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SenderKeyName")
+            .field("group_id", &self.group_id())
+            .field("sender", &self.sender())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::DeviceId;
+
+    #[test]
+    fn sender_key_names_own_their_data() {
+        let group_id = vec![0xab; 16];
+        let sender_name = String::from("+14159998888");
+        let device_id = DeviceId::new(2).unwrap();
+
+        let name = SenderKeyName::new(
+            &group_id,
+            &Address::new(&sender_name, device_id),
+        );
+        drop(group_id);
+        drop(sender_name);
+
+        assert_eq!(name.group_id(), &[0xab; 16][..]);
+        assert_eq!(name.sender().as_str().unwrap(), "+14159998888");
+        assert_eq!(name.sender().device_id(), device_id);
+    }
+
+    #[test]
+    fn round_trip_through_the_raw_struct() {
+        let sender = Address::new("+14159998888", DeviceId::new(2).unwrap());
+        let name = SenderKeyName::new(b"group", &sender);
+
+        let got = unsafe { SenderKeyName::from_raw(name.raw()) };
+
+        assert_eq!(got, name);
+    }
+
+    #[test]
+    fn null_group_ids_are_empty() {
+        let sender = Address::new("+14159998888", DeviceId::new(2).unwrap());
+        let raw = sys::signal_protocol_sender_key_name {
+            group_id: std::ptr::null(),
+            group_id_len: 0,
+            sender: unsafe { *sender.raw() },
+        };
+
+        let got = unsafe { SenderKeyName::from_raw(&raw) };
+
+        assert!(got.group_id().is_empty());
+        assert_eq!(got.sender().as_str().unwrap(), "+14159998888");
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{
+    buffer::Buffer, errors::InternalError, sender_key_name::SenderKeyName,
+};
+use std::os::raw::{c_int, c_void};
+
+/// Persists the sender keys used for group messaging, keyed by their
+/// [`SenderKeyName`].
+pub trait SenderKeyStore {
+    /// Load a serialized sender key record, returning `None` if there isn't
+    /// one yet.
+    fn load(
+        &self,
+        name: &SenderKeyName,
+    ) -> Result<Option<Buffer>, InternalError>;
+    fn store(
+        &self,
+        name: &SenderKeyName,
+        record: &[u8],
+    ) -> Result<(), InternalError>;
+}
+
+pub(crate) fn new_vtable<S: SenderKeyStore + 'static>(
+    store: S,
+) -> sys::signal_protocol_sender_key_store {
+    let state: Box<State> = Box::new(State(Box::new(store)));
+
+    sys::signal_protocol_sender_key_store {
+        user_data: Box::into_raw(state) as *mut c_void,
+        store_sender_key: Some(store_sender_key),
+        load_sender_key: Some(load_sender_key),
+        destroy_func: Some(destroy_func),
+    }
+}
+
+struct State(Box<dyn SenderKeyStore>);
+
+unsafe extern "C" fn store_sender_key(
+    sender_key_name: *const sys::signal_protocol_sender_key_name,
+    record: *mut u8,
+    record_len: usize,
+    _user_record: *mut u8,
+    _user_record_len: usize,
+    user_data: *mut c_void,
+) -> c_int {
+    assert!(!user_data.is_null());
+    assert!(!sender_key_name.is_null());
+    assert!(!record.is_null());
+    let user_data = &*(user_data as *const State);
+    let name = SenderKeyName::from_raw(sender_key_name);
+    let data = std::slice::from_raw_parts(record, record_len);
+
+    match user_data.0.store(&name, data) {
+        Ok(_) => sys::SG_SUCCESS as c_int,
+        Err(e) => e.code(),
+    }
+}
+
+unsafe extern "C" fn load_sender_key(
+    record: *mut *mut sys::signal_buffer,
+    user_record: *mut *mut sys::signal_buffer,
+    sender_key_name: *const sys::signal_protocol_sender_key_name,
+    user_data: *mut c_void,
+) -> c_int {
+    assert!(!user_data.is_null());
+    assert!(!sender_key_name.is_null());
+    assert!(!record.is_null());
+    let user_data = &*(user_data as *const State);
+    let name = SenderKeyName::from_raw(sender_key_name);
+
+    if !user_record.is_null() {
+        *user_record = std::ptr::null_mut();
+    }
+
+    // returns 1 if the record was found, 0 if it wasn't
+    match user_data.0.load(&name) {
+        Ok(Some(buffer)) => {
+            *record = buffer.into_raw();
+            1
+        },
+        Ok(None) => 0,
+        Err(e) => e.code(),
+    }
+}
+
+unsafe extern "C" fn destroy_func(user_data: *mut c_void) {
+    if !user_data.is_null() {
+        let user_data = Box::from_raw(user_data as *mut State);
+        drop(user_data);
+    }
+}
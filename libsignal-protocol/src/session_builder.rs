@@ -1,13 +1,22 @@
 use crate::{
-    address::Address,
+    address::{Address, OwnedAddress},
     context::{Context, ContextInner},
+    errors::FromInternalErrorCode,
+    keys::PublicKey,
     pre_key_bundle::PreKeyBundle,
     store_context::{StoreContext, StoreContextInner},
 };
-use std::{ptr, rc::Rc};
+use failure::Error;
+use std::{
+    fmt::{self, Debug, Formatter},
+    ptr,
+    rc::Rc,
+};
 
 pub struct SessionBuilder {
     raw: *mut sys::session_builder,
+    // `session_builder` keeps a pointer to the address it was created with
+    address: OwnedAddress,
     // both these fields must outlive `session_builder`
     _store_ctx: Rc<StoreContextInner>,
     _ctx: Rc<ContextInner>,
@@ -18,7 +27,9 @@ impl SessionBuilder {
         ctx: &Context,
         store_context: StoreContext,
         address: Address,
-    ) -> SessionBuilder {
+    ) -> Result<SessionBuilder, Error> {
+        let address = OwnedAddress::new(&address);
+
         unsafe {
             let mut raw = ptr::null_mut();
             sys::session_builder_create(
@@ -26,23 +37,74 @@ impl SessionBuilder {
                 store_context.raw(),
                 address.raw(),
                 ctx.raw(),
-            );
+            )
+            .into_result()?;
 
-            SessionBuilder {
+            Ok(SessionBuilder {
                 raw,
+                address,
                 _store_ctx: store_context.0,
                 _ctx: Rc::clone(&ctx.0),
-            }
+            })
         }
     }
 
-    pub fn process_pre_key_bundle(&self, pre_key_bundle: &PreKeyBundle) {
+    /// The address of the recipient sessions are built with.
+    pub fn address(&self) -> Address<'_> { self.address.as_address() }
+
+    /// Build a new session from a [`PreKeyBundle`] retrieved from a server.
+    ///
+    /// The most interesting errors are [`InternalError::UntrustedIdentity`]
+    /// when the [`IdentityKeyStore`] doesn't trust the bundle's identity key,
+    /// and [`InternalError::InvalidKey`] when the signed pre-key's signature
+    /// is invalid or the bundle doesn't contain one.
+    ///
+    /// [`InternalError::UntrustedIdentity`]: crate::InternalError::UntrustedIdentity
+    /// [`InternalError::InvalidKey`]: crate::InternalError::InvalidKey
+    /// [`IdentityKeyStore`]: crate::IdentityKeyStore
+    pub fn process_pre_key_bundle(
+        &self,
+        pre_key_bundle: &PreKeyBundle,
+    ) -> Result<(), Error> {
+        self.process_pre_key_bundle_with_callback(pre_key_bundle, |_| Ok(()))
+    }
+
+    /// Build a new session from a [`PreKeyBundle`], first calling
+    /// `callback` with the bundle's identity key.
+    ///
+    /// If the callback fails (e.g. because the key doesn't match the one a
+    /// directory has pinned for this address) its error is returned as-is,
+    /// and neither a session nor the identity is saved. This check happens
+    /// on top of the [`IdentityKeyStore`]'s usual trust decision.
+    ///
+    /// [`IdentityKeyStore`]: crate::IdentityKeyStore
+    pub fn process_pre_key_bundle_with_callback<F>(
+        &self,
+        pre_key_bundle: &PreKeyBundle,
+        callback: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce(&PublicKey) -> Result<(), Error>,
+    {
+        callback(&pre_key_bundle.identity_key())?;
+
         unsafe {
             sys::session_builder_process_pre_key_bundle(
                 self.raw,
                 pre_key_bundle.raw.as_ptr(),
-            );
+            )
+            .into_result()?;
         }
+
+        Ok(())
+    }
+}
+
+impl Debug for SessionBuilder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionBuilder")
+            .field("address", &self.address)
+            .finish()
     }
 }
 
@@ -0,0 +1,424 @@
+use crate::{
+    address::{Address, OwnedAddress},
+    context::{Context, ContextInner},
+    errors::{FromInternalErrorCode, InternalError},
+    messages::{CiphertextMessage, PreKeySignalMessage, SignalMessage},
+    padding::Padding,
+    store_context::{StoreContext, StoreContextInner},
+    Buffer,
+};
+use failure::Error;
+use std::{
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+    os::raw::{c_int, c_void},
+    ptr,
+    rc::Rc,
+};
+
+/// The ciphertext version every message is created with.
+const CURRENT_VERSION: u8 = sys::CIPHERTEXT_CURRENT_VERSION as u8;
+
+/// Encrypts and decrypts messages for a session with a single recipient.
+///
+/// This is a cheap handle around shared state, so cloning it gives another
+/// reference to the same underlying cipher. The padding and accepted
+/// versions belong to each handle.
+#[derive(Clone)]
+pub struct SessionCipher {
+    inner: Rc<SessionCipherInner>,
+    padding: Option<Padding>,
+    versions: RangeInclusive<u8>,
+}
+
+struct SessionCipherInner {
+    raw: *mut sys::session_cipher,
+    // `session_cipher` keeps a pointer to the address it was created with
+    address: OwnedAddress,
+    // both these fields must outlive `session_cipher`
+    _store_ctx: Rc<StoreContextInner>,
+    _ctx: Rc<ContextInner>,
+}
+
+impl SessionCipher {
+    pub fn new(
+        ctx: &Context,
+        store_context: &StoreContext,
+        address: &Address<'_>,
+    ) -> Result<SessionCipher, Error> {
+        let address = OwnedAddress::new(address);
+
+        unsafe {
+            let mut raw = ptr::null_mut();
+            sys::session_cipher_create(
+                &mut raw,
+                store_context.raw(),
+                address.raw(),
+                ctx.raw(),
+            )
+            .into_result()?;
+            sys::session_cipher_set_decryption_callback(
+                raw,
+                Some(decryption_callback),
+            );
+
+            Ok(SessionCipher {
+                inner: Rc::new(SessionCipherInner {
+                    raw,
+                    address,
+                    _store_ctx: Rc::clone(&store_context.0),
+                    _ctx: Rc::clone(&ctx.0),
+                }),
+                padding: None,
+                versions: CURRENT_VERSION..=CURRENT_VERSION,
+            })
+        }
+    }
+
+    /// Pad messages before they are encrypted and strip the padding after
+    /// they are decrypted, so message lengths don't leak.
+    ///
+    /// Both sides of a session must agree on whether messages are padded.
+    pub fn with_padding(mut self, padding: Padding) -> SessionCipher {
+        self.padding = Some(padding);
+        self
+    }
+
+    pub fn padding(&self) -> Option<Padding> { self.padding }
+
+    /// Only accept messages whose ciphertext version is within `versions`.
+    ///
+    /// Messages older than the minimum fail with
+    /// [`InternalError::LegacyMessage`] and messages newer than the maximum
+    /// fail with [`InternalError::InvalidVersion`], before anything is
+    /// decrypted. By default only the current version (3) is accepted,
+    /// which is the only one `libsignal-protocol-c` can decrypt anyway.
+    pub fn with_versions(
+        mut self,
+        versions: RangeInclusive<u8>,
+    ) -> SessionCipher {
+        self.versions = versions;
+        self
+    }
+
+    /// The range of ciphertext versions this cipher will decrypt.
+    pub fn versions(&self) -> RangeInclusive<u8> { self.versions.clone() }
+
+    /// The address of the recipient this cipher talks to.
+    pub fn address(&self) -> Address<'_> { self.inner.address.as_address() }
+
+    /// The registration ID of the remote client, as recorded in the current
+    /// session with them. This is 0 if there isn't a session yet.
+    pub fn remote_registration_id(&self) -> Result<u32, Error> {
+        unsafe {
+            let mut id = 0;
+            sys::session_cipher_get_remote_registration_id(
+                self.inner.raw,
+                &mut id,
+            )
+            .into_result()?;
+
+            Ok(id)
+        }
+    }
+
+    /// The version of the protocol the session was negotiated with (3 for
+    /// all sessions `libsignal-protocol-c` can create).
+    ///
+    /// Fails if there isn't a session with the recipient yet.
+    pub fn session_version(&self) -> Result<u32, Error> {
+        unsafe {
+            let mut version = 0;
+            sys::session_cipher_get_session_version(
+                self.inner.raw,
+                &mut version,
+            )
+            .into_result()?;
+
+            Ok(version)
+        }
+    }
+
+    /// Encrypt a message, producing a [`PreKeySignalMessage`] until the
+    /// recipient has responded, and a [`SignalMessage`] after that.
+    pub fn encrypt(&self, message: &[u8]) -> Result<CiphertextMessage, Error> {
+        let padded;
+        let message = match self.padding {
+            Some(padding) => {
+                padded = padding.pad(message);
+                &padded[..]
+            },
+            None => message,
+        };
+
+        unsafe {
+            let mut raw = ptr::null_mut();
+            sys::session_cipher_encrypt(
+                self.inner.raw,
+                message.as_ptr(),
+                message.len(),
+                &mut raw,
+            )
+            .into_result()?;
+
+            Ok(CiphertextMessage::from_raw(raw))
+        }
+    }
+
+    /// Decrypt a message which sets up a new session, saving the session
+    /// and removing the one-time pre-key it used.
+    pub fn decrypt_pre_key_signal_message(
+        &self,
+        message: &PreKeySignalMessage,
+    ) -> Result<Buffer, Error> {
+        self.decrypt_pre_key_signal_message_with_callback(message, |_| Ok(()))
+    }
+
+    /// Decrypt a [`PreKeySignalMessage`], calling `callback` with the
+    /// plaintext before any stores are updated.
+    ///
+    /// If the callback fails, the session isn't saved and the one-time
+    /// pre-key isn't removed, so the message can be decrypted again later.
+    /// This lets the plaintext be persisted atomically with the session.
+    pub fn decrypt_pre_key_signal_message_with_callback<F>(
+        &self,
+        message: &PreKeySignalMessage,
+        callback: F,
+    ) -> Result<Buffer, Error>
+    where
+        F: FnOnce(&[u8]) -> Result<(), Error>,
+    {
+        check_version(&self.versions, message.message_version())?;
+
+        let padding = self.padding;
+        let mut state = DecryptionCallback::new(move |plaintext: &[u8]| {
+            callback(strip_padding(padding, plaintext)?)
+        });
+
+        unsafe {
+            let mut plaintext = ptr::null_mut();
+            let result = sys::session_cipher_decrypt_pre_key_signal_message(
+                self.inner.raw,
+                message.raw.as_ptr(),
+                state.as_decrypt_context(),
+                &mut plaintext,
+            );
+            state.check(result)?;
+
+            self.unpad(Buffer::from_raw(plaintext))
+        }
+    }
+
+    /// Decrypt a message sent within an existing session.
+    pub fn decrypt_signal_message(
+        &self,
+        message: &SignalMessage,
+    ) -> Result<Buffer, Error> {
+        self.decrypt_signal_message_with_callback(message, |_| Ok(()))
+    }
+
+    /// Decrypt a [`SignalMessage`], calling `callback` with the plaintext
+    /// before the updated session is saved.
+    ///
+    /// If the callback fails, the session isn't saved.
+    pub fn decrypt_signal_message_with_callback<F>(
+        &self,
+        message: &SignalMessage,
+        callback: F,
+    ) -> Result<Buffer, Error>
+    where
+        F: FnOnce(&[u8]) -> Result<(), Error>,
+    {
+        check_version(&self.versions, message.message_version())?;
+
+        let padding = self.padding;
+        let mut state = DecryptionCallback::new(move |plaintext: &[u8]| {
+            callback(strip_padding(padding, plaintext)?)
+        });
+
+        unsafe {
+            let mut plaintext = ptr::null_mut();
+            let result = sys::session_cipher_decrypt_signal_message(
+                self.inner.raw,
+                message.raw.as_ptr(),
+                state.as_decrypt_context(),
+                &mut plaintext,
+            );
+            state.check(result)?;
+
+            self.unpad(Buffer::from_raw(plaintext))
+        }
+    }
+
+    fn unpad(&self, plaintext: Buffer) -> Result<Buffer, Error> {
+        match self.padding {
+            Some(padding) => {
+                padding.unpad(plaintext.as_slice()).map(Buffer::from)
+            },
+            None => Ok(plaintext),
+        }
+    }
+}
+
+fn check_version(
+    versions: &RangeInclusive<u8>,
+    version: u8,
+) -> Result<(), Error> {
+    if version < *versions.start() {
+        Err(InternalError::LegacyMessage.into())
+    } else if version > *versions.end() {
+        Err(InternalError::InvalidVersion.into())
+    } else {
+        Ok(())
+    }
+}
+
+fn strip_padding(
+    padding: Option<Padding>,
+    plaintext: &[u8],
+) -> Result<&[u8], Error> {
+    match padding {
+        Some(padding) => padding.unpad(plaintext),
+        None => Ok(plaintext),
+    }
+}
+
+type BoxedCallback<'a> = Box<dyn FnOnce(&[u8]) -> Result<(), Error> + 'a>;
+
+/// The closure passed to one of the `*_with_callback()` decrypt methods,
+/// handed to the cipher's decryption callback as the `decrypt_context`.
+pub(crate) struct DecryptionCallback<'a> {
+    callback: Option<BoxedCallback<'a>>,
+    error: Option<Error>,
+}
+
+impl<'a> DecryptionCallback<'a> {
+    pub(crate) fn new<F>(callback: F) -> DecryptionCallback<'a>
+    where
+        F: FnOnce(&[u8]) -> Result<(), Error> + 'a,
+    {
+        DecryptionCallback {
+            callback: Some(Box::new(callback)),
+            error: None,
+        }
+    }
+
+    pub(crate) fn as_decrypt_context(&mut self) -> *mut c_void {
+        self as *mut DecryptionCallback<'a> as *mut c_void
+    }
+
+    /// Turn the return code from a decrypt function into a [`Result`],
+    /// preferring the callback's error if it failed.
+    pub(crate) fn check(self, result: c_int) -> Result<(), Error> {
+        match self.error {
+            Some(err) => Err(err),
+            None => result.into_result().map_err(Error::from),
+        }
+    }
+
+    /// Invoke the callback from the C side.
+    pub(crate) unsafe fn invoke(
+        decrypt_context: *mut c_void,
+        plaintext: *mut sys::signal_buffer,
+    ) -> c_int {
+        assert!(!decrypt_context.is_null());
+        assert!(!plaintext.is_null());
+        let state = &mut *(decrypt_context as *mut DecryptionCallback<'_>);
+        let plaintext = std::slice::from_raw_parts(
+            sys::signal_buffer_data(plaintext),
+            sys::signal_buffer_len(plaintext),
+        );
+
+        match state.callback.take().map(|callback| callback(plaintext)) {
+            Some(Err(e)) => {
+                state.error = Some(e);
+                InternalError::Unknown.code()
+            },
+            _ => sys::SG_SUCCESS as c_int,
+        }
+    }
+}
+
+unsafe extern "C" fn decryption_callback(
+    _cipher: *mut sys::session_cipher,
+    plaintext: *mut sys::signal_buffer,
+    decrypt_context: *mut c_void,
+) -> c_int {
+    DecryptionCallback::invoke(decrypt_context, plaintext)
+}
+
+impl Debug for SessionCipher {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionCipher")
+            .field("address", &self.inner.address)
+            .field("padding", &self.padding)
+            .field("versions", &self.versions)
+            .finish()
+    }
+}
+
+impl Drop for SessionCipherInner {
+    fn drop(&mut self) {
+        unsafe {
+            sys::session_cipher_free(self.raw);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn callback_sees_the_plaintext() {
+        let plaintext = Buffer::from(&b"Hello, World!"[..]).into_raw();
+        let mut seen = Vec::new();
+        let mut state = DecryptionCallback::new(|data: &[u8]| {
+            seen.extend_from_slice(data);
+            Ok(())
+        });
+
+        let ret = unsafe {
+            DecryptionCallback::invoke(state.as_decrypt_context(), plaintext)
+        };
+        let plaintext = unsafe { Buffer::from_raw(plaintext) };
+
+        assert!(state.check(ret).is_ok());
+        assert_eq!(seen, plaintext.as_slice());
+    }
+
+    #[test]
+    fn callback_errors_abort_decryption() {
+        let plaintext = Buffer::from(&b"Hello, World!"[..]).into_raw();
+        let mut state = DecryptionCallback::new(|_: &[u8]| {
+            Err(failure::err_msg("Unable to save the message"))
+        });
+
+        let ret = unsafe {
+            DecryptionCallback::invoke(state.as_decrypt_context(), plaintext)
+        };
+        drop(unsafe { Buffer::from_raw(plaintext) });
+
+        assert_ne!(ret, sys::SG_SUCCESS as c_int);
+        let err = state.check(ret).unwrap_err();
+        assert_eq!(err.to_string(), "Unable to save the message");
+    }
+
+    #[test]
+    fn messages_outside_the_pinned_versions_are_rejected() {
+        let versions = 3..=3;
+        let inputs = vec![
+            (2, Some(InternalError::LegacyMessage)),
+            (3, None),
+            (4, Some(InternalError::InvalidVersion)),
+        ];
+
+        for (version, should_be) in inputs {
+            let got = check_version(&versions, version)
+                .err()
+                .map(|e| *e.downcast_ref::<InternalError>().unwrap());
+
+            assert_eq!(got, should_be, "version {}", version);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{
+    errors::FromInternalErrorCode, raw_ptr::Raw, session_state::SessionState,
+    Buffer, Context,
+};
+use failure::Error;
+use std::{
+    fmt::{self, Debug, Formatter},
+    io::Write,
+    ptr,
+};
+
+/// Everything known about the sessions with a particular [`Address`], in
+/// the form a [`SessionStore`] saves and loads.
+///
+/// [`Address`]: crate::Address
+/// [`SessionStore`]: crate::SessionStore
+#[derive(Clone)]
+pub struct SessionRecord {
+    pub(crate) raw: Raw<sys::session_record>,
+}
+
+impl SessionRecord {
+    /// Create an empty record, without any sessions.
+    pub fn new(ctx: &Context) -> Result<SessionRecord, Error> {
+        unsafe {
+            let mut raw = ptr::null_mut();
+            sys::session_record_create(&mut raw, ptr::null_mut(), ctx.raw())
+                .into_result()?;
+
+            Ok(SessionRecord {
+                raw: Raw::from_ptr(raw),
+            })
+        }
+    }
+
+    pub fn deserialize(
+        ctx: &Context,
+        data: &[u8],
+    ) -> Result<SessionRecord, Error> {
+        unsafe { SessionRecord::deserialize_with_raw_context(ctx.raw(), data) }
+    }
+
+    unsafe fn deserialize_with_raw_context(
+        ctx: *mut sys::signal_context,
+        data: &[u8],
+    ) -> Result<SessionRecord, Error> {
+        let mut raw = ptr::null_mut();
+        sys::session_record_deserialize(
+            &mut raw,
+            data.as_ptr(),
+            data.len(),
+            ctx,
+        )
+        .into_result()?;
+
+        Ok(SessionRecord {
+            raw: Raw::from_ptr(raw),
+        })
+    }
+
+    pub fn serialize_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let buffer = self.serialize()?;
+        writer.write_all(buffer.as_slice())?;
+
+        Ok(())
+    }
+
+    pub fn serialize(&self) -> Result<Buffer, Error> {
+        unsafe {
+            let mut buffer = ptr::null_mut();
+            sys::session_record_serialize(&mut buffer, self.raw.as_const_ptr())
+                .into_result()?;
+            Ok(Buffer::from_raw(buffer))
+        }
+    }
+
+    /// Is this a new record which has never had a session?
+    pub fn is_fresh(&self) -> bool {
+        unsafe { sys::session_record_is_fresh(self.raw.as_ptr()) != 0 }
+    }
+
+    /// The session currently used to send messages.
+    pub fn state(&self) -> SessionState {
+        unsafe {
+            let raw = sys::session_record_get_state(self.raw.as_ptr());
+            assert!(!raw.is_null());
+
+            SessionState {
+                raw: Raw::copied_from(raw),
+            }
+        }
+    }
+
+    /// Older sessions which are kept around to decrypt messages which were
+    /// delayed, most recent first.
+    pub fn previous_states(&self) -> Vec<SessionState> {
+        let mut states = Vec::new();
+
+        unsafe {
+            let mut node = sys::session_record_get_previous_states_head(
+                self.raw.as_const_ptr(),
+            );
+
+            while !node.is_null() {
+                let raw = sys::session_record_get_previous_states_element(node);
+                states.push(SessionState {
+                    raw: Raw::copied_from(raw),
+                });
+                node = sys::session_record_get_previous_states_next(node);
+            }
+        }
+
+        states
+    }
+}
+
+impl_try_from_bytes!(
+    SessionRecord,
+    SessionRecord::deserialize_with_raw_context
+);
+
+impl Debug for SessionRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionRecord")
+            .field("is_fresh", &self.is_fresh())
+            .field("state", &self.state())
+            .field("previous_states", &self.previous_states().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn empty_records_round_trip() {
+        let ctx = Context::default();
+        let record = SessionRecord::new(&ctx).unwrap();
+        assert!(record.is_fresh());
+
+        let serialized = record.serialize().unwrap();
+        let got = SessionRecord::try_from(serialized.as_slice()).unwrap();
+
+        assert_eq!(got.serialize().unwrap(), serialized);
+    }
+
+    #[test]
+    fn fresh_records_have_an_empty_state() {
+        let ctx = Context::default();
+        let record = SessionRecord::new(&ctx).unwrap();
+
+        let state = record.state();
+
+        assert!(state.remote_identity_key().is_none());
+        assert!(state.alice_base_key().is_none());
+        assert!(!state.has_unacknowledged_pre_key_message());
+        assert!(record.previous_states().is_empty());
+    }
+}
@@ -0,0 +1,78 @@
+use crate::{keys::PublicKey, raw_ptr::Raw};
+use std::fmt::{self, Debug, Formatter};
+
+/// A single session within a [`SessionRecord`](crate::SessionRecord).
+#[derive(Clone)]
+pub struct SessionState {
+    pub(crate) raw: Raw<sys::session_state>,
+}
+
+impl SessionState {
+    /// The protocol version the session was negotiated with.
+    pub fn session_version(&self) -> u32 {
+        unsafe {
+            sys::session_state_get_session_version(self.raw.as_const_ptr())
+        }
+    }
+
+    pub fn local_identity_key(&self) -> Option<PublicKey> {
+        unsafe {
+            public_key(sys::session_state_get_local_identity_key(
+                self.raw.as_const_ptr(),
+            ))
+        }
+    }
+
+    pub fn remote_identity_key(&self) -> Option<PublicKey> {
+        unsafe {
+            public_key(sys::session_state_get_remote_identity_key(
+                self.raw.as_const_ptr(),
+            ))
+        }
+    }
+
+    /// The base key the session initiator ("Alice") used, which identifies
+    /// the session.
+    pub fn alice_base_key(&self) -> Option<PublicKey> {
+        unsafe {
+            public_key(sys::session_state_get_alice_base_key(
+                self.raw.as_const_ptr(),
+            ))
+        }
+    }
+
+    /// Have we started this session without hearing back from the other
+    /// side yet? If so, every message we send is still a
+    /// [`PreKeySignalMessage`](crate::PreKeySignalMessage).
+    pub fn has_unacknowledged_pre_key_message(&self) -> bool {
+        unsafe {
+            sys::session_state_has_unacknowledged_pre_key_message(
+                self.raw.as_const_ptr(),
+            ) != 0
+        }
+    }
+}
+
+/// Borrow a key owned by the `session_state`, which may not have been set.
+unsafe fn public_key(raw: *mut sys::ec_public_key) -> Option<PublicKey> {
+    if raw.is_null() {
+        None
+    } else {
+        Some(PublicKey {
+            raw: Raw::copied_from(raw),
+        })
+    }
+}
+
+impl Debug for SessionState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionState")
+            .field("session_version", &self.session_version())
+            .field("remote_identity_key", &self.remote_identity_key())
+            .field(
+                "has_unacknowledged_pre_key_message",
+                &self.has_unacknowledged_pre_key_message(),
+            )
+            .finish()
+    }
+}
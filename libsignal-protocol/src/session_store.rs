@@ -1,12 +1,52 @@
-use crate::{errors::InternalError, Address, Buffer};
-use std::os::raw::{c_char, c_int, c_void};
+use crate::{errors::InternalError, Address, Buffer, DeviceId};
+use std::{
+    os::raw::{c_char, c_int, c_void},
+    slice, str,
+};
 
+/// Where session state is persisted between messages.
 pub trait SessionStore {
+    /// Load the serialized [`SessionRecord`] for an address, along with any
+    /// application-specific data stored alongside it. An empty record means
+    /// there is no session.
+    ///
+    /// [`SessionRecord`]: crate::SessionRecord
     fn load_session(
         &self,
         address: &Address,
     ) -> Result<(Buffer, Buffer), InternalError>;
-    fn get_sub_devuce_sessions(&self);
+
+    /// Get the IDs of every device we have a session with for `name`.
+    fn get_sub_device_sessions(
+        &self,
+        name: &str,
+    ) -> Result<Vec<DeviceId>, InternalError>;
+
+    /// Save the serialized [`SessionRecord`] for an address, replacing
+    /// whatever was there before. The `user_record` is
+    /// application-specific data to keep alongside it, and may be empty.
+    ///
+    /// [`SessionRecord`]: crate::SessionRecord
+    fn store_session(
+        &self,
+        address: &Address,
+        record: &[u8],
+        user_record: &[u8],
+    ) -> Result<(), InternalError>;
+
+    /// Is there a session for this address?
+    fn contains_session(
+        &self,
+        address: &Address,
+    ) -> Result<bool, InternalError>;
+
+    /// Delete the session for an address, returning whether there was one
+    /// to delete.
+    fn delete_session(&self, address: &Address) -> Result<bool, InternalError>;
+
+    /// Delete the sessions for every device belonging to `name`, returning
+    /// how many were deleted.
+    fn delete_all_sessions(&self, name: &str) -> Result<usize, InternalError>;
 }
 
 pub(crate) fn new_vtable<S: SessionStore + 'static>(
@@ -29,54 +69,152 @@ pub(crate) fn new_vtable<S: SessionStore + 'static>(
 struct State(Box<dyn SessionStore>);
 
 unsafe extern "C" fn load_session_func(
-    _record: *mut *mut sys::signal_buffer,
-    _user_record: *mut *mut sys::signal_buffer,
-    _address: *const sys::signal_protocol_address,
-    _user_data: *mut c_void,
+    record: *mut *mut sys::signal_buffer,
+    user_record: *mut *mut sys::signal_buffer,
+    address: *const sys::signal_protocol_address,
+    user_data: *mut c_void,
 ) -> c_int {
-    unimplemented!()
+    assert!(!user_data.is_null());
+    assert!(!record.is_null());
+    let user_data = &*(user_data as *const State);
+    let address = Address::from_raw(address);
+
+    // returns 1 if the session was found, 0 if it wasn't
+    match user_data.0.load_session(&address) {
+        Ok((session, _)) if session.is_empty() => 0,
+        Ok((session, user)) => {
+            *record = session.into_raw();
+            if !user_record.is_null() && !user.is_empty() {
+                *user_record = user.into_raw();
+            }
+            1
+        },
+        Err(e) => e.code(),
+    }
 }
 
 unsafe extern "C" fn get_sub_device_sessions_func(
-    _sessions: *mut *mut sys::signal_int_list,
-    _name: *const c_char,
-    _name_len: usize,
-    _user_data: *mut c_void,
+    sessions: *mut *mut sys::signal_int_list,
+    name: *const c_char,
+    name_len: usize,
+    user_data: *mut c_void,
 ) -> c_int {
-    unimplemented!()
+    assert!(!user_data.is_null());
+    assert!(!sessions.is_null());
+    let user_data = &*(user_data as *const State);
+    let name = match name_from_raw(name, name_len) {
+        Some(name) => name,
+        None => return InternalError::InvalidArgument.code(),
+    };
+
+    let device_ids = match user_data.0.get_sub_device_sessions(name) {
+        Ok(ids) => ids,
+        Err(e) => return e.code(),
+    };
+
+    let list = sys::signal_int_list_alloc();
+    if list.is_null() {
+        return InternalError::NoMemory.code();
+    }
+
+    for id in &device_ids {
+        let ret = sys::signal_int_list_push_back(list, id.raw());
+        if ret < 0 {
+            sys::signal_int_list_free(list);
+            return ret;
+        }
+    }
+
+    // returns the number of device IDs in the list
+    *sessions = list;
+    device_ids.len() as c_int
 }
 
 unsafe extern "C" fn store_session_func(
-    _address: *const sys::signal_protocol_address,
-    _record: *mut u8,
-    _record_len: usize,
-    _user_record: *mut u8,
-    _user_record_len: usize,
-    _user_data: *mut c_void,
+    address: *const sys::signal_protocol_address,
+    record: *mut u8,
+    record_len: usize,
+    user_record: *mut u8,
+    user_record_len: usize,
+    user_data: *mut c_void,
 ) -> c_int {
-    unimplemented!()
+    assert!(!user_data.is_null());
+    assert!(!record.is_null());
+    let user_data = &*(user_data as *const State);
+    let address = Address::from_raw(address);
+    let record = slice::from_raw_parts(record, record_len);
+    let user_record = if user_record.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(user_record, user_record_len)
+    };
+
+    match user_data.0.store_session(&address, record, user_record) {
+        Ok(_) => sys::SG_SUCCESS as c_int,
+        Err(e) => e.code(),
+    }
 }
 
 unsafe extern "C" fn contains_session_func(
-    _address: *const sys::signal_protocol_address,
-    _user_data: *mut c_void,
+    address: *const sys::signal_protocol_address,
+    user_data: *mut c_void,
 ) -> c_int {
-    unimplemented!()
+    assert!(!user_data.is_null());
+    let user_data = &*(user_data as *const State);
+    let address = Address::from_raw(address);
+
+    match user_data.0.contains_session(&address) {
+        Ok(found) => found as c_int,
+        Err(e) => e.code(),
+    }
 }
 
 unsafe extern "C" fn delete_session_func(
-    _address: *const sys::signal_protocol_address,
-    _user_data: *mut c_void,
+    address: *const sys::signal_protocol_address,
+    user_data: *mut c_void,
 ) -> c_int {
-    unimplemented!()
+    assert!(!user_data.is_null());
+    let user_data = &*(user_data as *const State);
+    let address = Address::from_raw(address);
+
+    // returns 1 if a session was deleted, 0 if there wasn't one
+    match user_data.0.delete_session(&address) {
+        Ok(deleted) => deleted as c_int,
+        Err(e) => e.code(),
+    }
 }
 
 unsafe extern "C" fn delete_all_sessions_func(
-    _name: *const c_char,
-    _name_len: usize,
-    _user_data: *mut c_void,
+    name: *const c_char,
+    name_len: usize,
+    user_data: *mut c_void,
 ) -> c_int {
-    unimplemented!()
+    assert!(!user_data.is_null());
+    let user_data = &*(user_data as *const State);
+    let name = match name_from_raw(name, name_len) {
+        Some(name) => name,
+        None => return InternalError::InvalidArgument.code(),
+    };
+
+    // returns the number of sessions deleted
+    match user_data.0.delete_all_sessions(name) {
+        Ok(deleted) => deleted as c_int,
+        Err(e) => e.code(),
+    }
+}
+
+/// Names are always created from a `&str` on the Rust side, so anything
+/// which isn't valid UTF-8 is an invalid argument.
+unsafe fn name_from_raw<'a>(
+    name: *const c_char,
+    len: usize,
+) -> Option<&'a str> {
+    if name.is_null() {
+        return None;
+    }
+
+    let bytes = slice::from_raw_parts(name as *const u8, len);
+    str::from_utf8(bytes).ok()
 }
 
 unsafe extern "C" fn destroy_func(user_data: *mut c_void) {
@@ -0,0 +1,132 @@
+use crate::{
+    errors::InternalError,
+    keys::{IdentityKeyPair, PublicKey},
+    Context,
+};
+use failure::Error;
+
+const SIGNATURE_LEN: usize = sys::CURVE_SIGNATURE_LEN as usize;
+
+/// Prepended to every payload before signing so a [`SignedPayload`] can never
+/// be confused with a protocol signature (e.g. over a signed pre-key), and
+/// vice versa.
+const DOMAIN_SEPARATOR: &[u8] = b"libsignal-protocol-rs signed payload v1\0";
+
+/// Arbitrary application data signed with an identity key.
+///
+/// This lets applications authenticate things like profile data or
+/// capability announcements using the same identity as the protocol. The
+/// serialized form is the payload followed by a 64 byte signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedPayload {
+    payload: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl SignedPayload {
+    /// Sign a payload with our identity key.
+    pub fn sign(
+        ctx: &Context,
+        identity: &IdentityKeyPair,
+        payload: &[u8],
+    ) -> Result<SignedPayload, Error> {
+        let signature = ctx.calculate_signature(
+            &identity.private_key()?,
+            &domain_separated(payload),
+        )?;
+
+        Ok(SignedPayload {
+            payload: payload.to_vec(),
+            signature: signature.as_slice().to_vec(),
+        })
+    }
+
+    /// Check a serialized [`SignedPayload`] was signed by `identity`,
+    /// returning it if the signature is valid.
+    pub fn verify(
+        identity: &PublicKey,
+        blob: &[u8],
+    ) -> Result<SignedPayload, Error> {
+        if blob.len() < SIGNATURE_LEN {
+            return Err(InternalError::InvalidMessage.into());
+        }
+
+        let (payload, signature) = blob.split_at(blob.len() - SIGNATURE_LEN);
+        identity.verify_signature(&domain_separated(payload), signature)?;
+
+        Ok(SignedPayload {
+            payload: payload.to_vec(),
+            signature: signature.to_vec(),
+        })
+    }
+
+    pub fn payload(&self) -> &[u8] { &self.payload }
+
+    pub fn signature(&self) -> &[u8] { &self.signature }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut blob =
+            Vec::with_capacity(self.payload.len() + self.signature.len());
+        blob.extend_from_slice(&self.payload);
+        blob.extend_from_slice(&self.signature);
+        blob
+    }
+}
+
+fn domain_separated(payload: &[u8]) -> Vec<u8> {
+    let mut message =
+        Vec::with_capacity(DOMAIN_SEPARATOR.len() + payload.len());
+    message.extend_from_slice(DOMAIN_SEPARATOR);
+    message.extend_from_slice(payload);
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_and_verify_a_payload() {
+        let ctx = Context::default();
+        let identity = ctx.generate_identity_key_pair().unwrap();
+        let public = identity.public_key().unwrap();
+
+        let signed =
+            SignedPayload::sign(&ctx, &identity, b"display name").unwrap();
+        let got = SignedPayload::verify(&public, &signed.serialize()).unwrap();
+
+        assert_eq!(got, signed);
+        assert_eq!(got.payload(), b"display name");
+    }
+
+    #[test]
+    fn tampered_payloads_are_rejected() {
+        let ctx = Context::default();
+        let identity = ctx.generate_identity_key_pair().unwrap();
+        let public = identity.public_key().unwrap();
+        let mut blob = SignedPayload::sign(&ctx, &identity, b"display name")
+            .unwrap()
+            .serialize();
+
+        blob[0] ^= 0x01;
+
+        assert!(SignedPayload::verify(&public, &blob).is_err());
+        assert!(SignedPayload::verify(&public, &blob[..10]).is_err());
+    }
+
+    #[test]
+    fn protocol_signatures_are_not_valid_payload_signatures() {
+        let ctx = Context::default();
+        let identity = ctx.generate_identity_key_pair().unwrap();
+        let public = identity.public_key().unwrap();
+        let payload = b"display name";
+        let raw_signature = ctx
+            .calculate_signature(&identity.private_key().unwrap(), payload)
+            .unwrap();
+
+        let mut blob = payload.to_vec();
+        blob.extend_from_slice(raw_signature.as_slice());
+
+        assert!(SignedPayload::verify(&public, &blob).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+/// The protobuf tag for a length-delimited field 1
+/// (`SignalMessage.ratchetKey`).
+const TAG_FIELD_1_BYTES: u8 = 0x0a;
+/// The protobuf tag for a varint field 1 (`PreKeySignalMessage.preKeyId` or
+/// `SenderKeyMessage.id`).
+const TAG_FIELD_1_VARINT: u8 = 0x08;
+/// The protobuf tag for a length-delimited field 2
+/// (`PreKeySignalMessage.baseKey`).
+const TAG_FIELD_2_BYTES: u8 = 0x12;
+/// The protobuf tag for a varint field 2 (`SenderKeyMessage.iteration`).
+const TAG_FIELD_2_VARINT: u8 = 0x10;
+/// The version sealed sender (v1) envelopes start with.
+const SEALED_SENDER_VERSION: u8 = 1;
+
+/// What kind of message a blob appears to be, according to [`sniff()`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MessageKind {
+    /// A message sent within an existing session.
+    Signal,
+    /// A message which also sets up a new session.
+    PreKeySignal,
+    /// A group message encrypted with a sender key.
+    SenderKey,
+    /// A sealed sender envelope.
+    SealedSender,
+    /// Not recognisably any of the above.
+    Unknown,
+}
+
+/// Cheaply guess what kind of message a blob is, without parsing it.
+///
+/// Only the version byte and the first protobuf field tags are inspected,
+/// so this is meant for routing a message to the right decrypt path and
+/// dropping obvious garbage early. A message classified as anything other
+/// than [`MessageKind::Unknown`] may still fail to parse.
+///
+/// Legacy (v1) Signal messages, which `libsignal-protocol-c` doesn't
+/// support anyway, share a version byte with sealed sender and are
+/// classified as [`MessageKind::SealedSender`].
+pub fn sniff(message: &[u8]) -> MessageKind {
+    let version = match message.first() {
+        Some(byte) => byte >> 4,
+        None => return MessageKind::Unknown,
+    };
+
+    if version == SEALED_SENDER_VERSION {
+        return MessageKind::SealedSender;
+    }
+    if u32::from(version) != sys::CIPHERTEXT_CURRENT_VERSION {
+        return MessageKind::Unknown;
+    }
+
+    match message.get(1) {
+        Some(&TAG_FIELD_1_BYTES) => MessageKind::Signal,
+        Some(&TAG_FIELD_2_BYTES) => MessageKind::PreKeySignal,
+        // both pre-key messages and sender key messages start with a
+        // varint, so look at the field after it
+        Some(&TAG_FIELD_1_VARINT) => match tag_after_varint(&message[2..]) {
+            Some(TAG_FIELD_2_BYTES) => MessageKind::PreKeySignal,
+            Some(TAG_FIELD_2_VARINT) => MessageKind::SenderKey,
+            _ => MessageKind::Unknown,
+        },
+        _ => MessageKind::Unknown,
+    }
+}
+
+/// Skip over a varint and return the byte following it.
+fn tag_after_varint(bytes: &[u8]) -> Option<u8> {
+    let end = bytes.iter().position(|byte| byte & 0x80 == 0)?;
+    bytes.get(end + 1).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_messages() {
+        let inputs = vec![
+            (&[][..], MessageKind::Unknown),
+            (&[0x33, 0x0a, 0x21][..], MessageKind::Signal),
+            (&[0x33, 0x12, 0x21][..], MessageKind::PreKeySignal),
+            (&[0x33, 0x08, 0x01, 0x12][..], MessageKind::PreKeySignal),
+            (&[0x33, 0x08, 0xac, 0x02, 0x10][..], MessageKind::SenderKey),
+            (&[0x11, 0x0a][..], MessageKind::SealedSender),
+            (&[0x33, 0x08, 0xff][..], MessageKind::Unknown),
+            (&[0x33, 0x42][..], MessageKind::Unknown),
+            (&[0x23, 0x0a][..], MessageKind::Unknown),
+        ];
+
+        for (message, should_be) in inputs {
+            assert_eq!(sniff(message), should_be, "{:02x?}", message);
+        }
+    }
+}
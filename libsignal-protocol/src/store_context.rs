@@ -1,6 +1,27 @@
-use crate::context::ContextInner;
-use std::rc::Rc;
+use crate::{
+    address::Address,
+    context::ContextInner,
+    errors::{FromInternalErrorCode, InternalError},
+    ids::{DeviceId, PreKeyId, SignedPreKeyId},
+    keys::{IdentityKeyPair, PreKey, SessionSignedPreKey},
+    pre_key_bundle::PreKeyBundle,
+    raw_ptr::Raw,
+    sender_key_store::{self as sks, SenderKeyStore},
+    session_record::SessionRecord,
+};
+use failure::Error;
+use std::{
+    cell::Cell,
+    fmt::{self, Debug, Formatter},
+    ptr,
+    rc::Rc,
+};
 
+/// The stores used by the protocol, registered with `libsignal-protocol-c`.
+///
+/// This is a cheap handle around shared state, so cloning it gives another
+/// reference to the same stores rather than registering them again.
+#[derive(Clone)]
 pub struct StoreContext(pub(crate) Rc<StoreContextInner>);
 
 impl StoreContext {
@@ -11,19 +32,264 @@ impl StoreContext {
         StoreContext(Rc::new(StoreContextInner {
             raw,
             ctx: Rc::clone(ctx),
+            has_sender_key_store: Cell::new(false),
         }))
     }
 
+    /// Register the [`SenderKeyStore`] needed for group messaging.
+    ///
+    /// This can only be done once, because `libsignal-protocol-c` has no way
+    /// of destroying a sender key store which gets replaced.
+    pub fn set_sender_key_store<S>(&self, store: S) -> Result<(), Error>
+    where
+        S: SenderKeyStore + 'static,
+    {
+        if self.0.has_sender_key_store.get() {
+            return Err(failure::err_msg(
+                "A sender key store has already been registered",
+            ));
+        }
+
+        unsafe {
+            let vtable = sks::new_vtable(store);
+            let result =
+                sys::signal_protocol_store_context_set_sender_key_store(
+                    self.raw(),
+                    &vtable,
+                );
+
+            if let Err(e) = result.into_result() {
+                // the store context didn't take ownership of our store
+                if let Some(destroy) = vtable.destroy_func {
+                    destroy(vtable.user_data);
+                }
+                return Err(e.into());
+            }
+        }
+        self.0.has_sender_key_store.set(true);
+
+        Ok(())
+    }
+
+    /// Load a one-time pre-key from the [`PreKeyStore`].
+    ///
+    /// [`PreKeyStore`]: crate::PreKeyStore
+    pub fn load_pre_key(&self, id: PreKeyId) -> Result<PreKey, Error> {
+        unsafe {
+            let mut raw = ptr::null_mut();
+            sys::signal_protocol_pre_key_load_key(
+                self.raw(),
+                &mut raw,
+                id.value(),
+            )
+            .into_result()?;
+
+            Ok(PreKey {
+                raw: Raw::from_ptr(raw),
+            })
+        }
+    }
+
+    /// Does the [`PreKeyStore`] contain a one-time pre-key with this ID?
+    ///
+    /// [`PreKeyStore`]: crate::PreKeyStore
+    pub fn contains_pre_key(&self, id: PreKeyId) -> Result<bool, Error> {
+        unsafe {
+            let result = sys::signal_protocol_pre_key_contains_key(
+                self.raw(),
+                id.value(),
+            );
+
+            contains_result(result)
+        }
+    }
+
+    /// Remove a one-time pre-key from the [`PreKeyStore`].
+    ///
+    /// [`PreKeyStore`]: crate::PreKeyStore
+    pub fn remove_pre_key(&self, id: PreKeyId) -> Result<(), Error> {
+        unsafe {
+            sys::signal_protocol_pre_key_remove_key(self.raw(), id.value())
+                .into_result()?;
+        }
+
+        Ok(())
+    }
+
+    /// Load a signed pre-key from the [`SignedPreKeyStore`].
+    ///
+    /// [`SignedPreKeyStore`]: crate::SignedPreKeyStore
+    pub fn load_signed_pre_key(
+        &self,
+        id: SignedPreKeyId,
+    ) -> Result<SessionSignedPreKey, Error> {
+        unsafe {
+            let mut raw = ptr::null_mut();
+            sys::signal_protocol_signed_pre_key_load_key(
+                self.raw(),
+                &mut raw,
+                id.value(),
+            )
+            .into_result()?;
+
+            Ok(SessionSignedPreKey {
+                raw: Raw::from_ptr(raw),
+            })
+        }
+    }
+
+    /// Does the [`SignedPreKeyStore`] contain a signed pre-key with this ID?
+    ///
+    /// [`SignedPreKeyStore`]: crate::SignedPreKeyStore
+    pub fn contains_signed_pre_key(
+        &self,
+        id: SignedPreKeyId,
+    ) -> Result<bool, Error> {
+        unsafe {
+            let result = sys::signal_protocol_signed_pre_key_contains_key(
+                self.raw(),
+                id.value(),
+            );
+
+            contains_result(result)
+        }
+    }
+
+    /// Remove a signed pre-key from the [`SignedPreKeyStore`].
+    ///
+    /// [`SignedPreKeyStore`]: crate::SignedPreKeyStore
+    pub fn remove_signed_pre_key(
+        &self,
+        id: SignedPreKeyId,
+    ) -> Result<(), Error> {
+        unsafe {
+            sys::signal_protocol_signed_pre_key_remove_key(
+                self.raw(),
+                id.value(),
+            )
+            .into_result()?;
+        }
+
+        Ok(())
+    }
+
+    /// Load the [`SessionRecord`] for an address from the [`SessionStore`].
+    ///
+    /// If there is no session with the address yet, a fresh record is
+    /// returned (see [`SessionRecord::is_fresh()`]).
+    ///
+    /// [`SessionStore`]: crate::SessionStore
+    pub fn load_session(
+        &self,
+        address: &Address<'_>,
+    ) -> Result<SessionRecord, Error> {
+        unsafe {
+            let mut raw = ptr::null_mut();
+            sys::signal_protocol_session_load_session(
+                self.raw(),
+                &mut raw,
+                address.raw(),
+            )
+            .into_result()?;
+
+            Ok(SessionRecord {
+                raw: Raw::from_ptr(raw),
+            })
+        }
+    }
+
+    /// Get the local identity from the [`IdentityKeyStore`].
+    ///
+    /// [`IdentityKeyStore`]: crate::IdentityKeyStore
+    pub fn identity_key_pair(&self) -> Result<IdentityKeyPair, Error> {
+        unsafe {
+            let mut raw = ptr::null_mut();
+            sys::signal_protocol_identity_get_key_pair(self.raw(), &mut raw)
+                .into_result()?;
+
+            Ok(IdentityKeyPair {
+                raw: Raw::from_ptr(raw),
+            })
+        }
+    }
+
+    /// Get the local registration ID from the [`IdentityKeyStore`].
+    ///
+    /// [`IdentityKeyStore`]: crate::IdentityKeyStore
+    pub fn local_registration_id(&self) -> Result<u32, Error> {
+        unsafe {
+            let mut id = 0;
+            sys::signal_protocol_identity_get_local_registration_id(
+                self.raw(),
+                &mut id,
+            )
+            .into_result()?;
+
+            Ok(id)
+        }
+    }
+
+    /// Assemble the [`PreKeyBundle`] other clients need to start a session
+    /// with this device, ready to be uploaded to a server or handed to a
+    /// peer.
+    ///
+    /// The bundle uses our identity and registration ID, the one-time
+    /// pre-key `pre_key_id`, and the signed pre-key `signed_pre_key_id`.
+    /// The stores don't keep track of which signed pre-key is current, so
+    /// the caller needs to say which one to use.
+    pub fn local_pre_key_bundle(
+        &self,
+        device_id: DeviceId,
+        pre_key_id: PreKeyId,
+        signed_pre_key_id: SignedPreKeyId,
+    ) -> Result<PreKeyBundle, Error> {
+        let identity = self.identity_key_pair()?;
+        let pre_key = self.load_pre_key(pre_key_id)?;
+        let signed_pre_key = self.load_signed_pre_key(signed_pre_key_id)?;
+
+        PreKeyBundle::builder()
+            .registration_id(self.local_registration_id()?)
+            .device_id(device_id)
+            .pre_key(pre_key.id(), &pre_key.key_pair().public()?)
+            .signed_pre_key(
+                signed_pre_key.id(),
+                &signed_pre_key.get_key_pair().public()?,
+            )
+            .signature(signed_pre_key.get_signature())
+            .identity_key(&identity.public_key()?)
+            .build()
+    }
+
     pub(crate) fn raw(&self) -> *mut sys::signal_protocol_store_context {
         self.0.raw
     }
 }
 
+/// The `*_contains_key()` functions return `1` or `0`, or a negative error
+/// code.
+fn contains_result(result: i32) -> Result<bool, Error> {
+    match result {
+        0 => Ok(false),
+        1 => Ok(true),
+        other => match InternalError::from_error_code(other) {
+            Some(err) => Err(err.into()),
+            None => Err(failure::format_err!("Unknown error code: {}", other)),
+        },
+    }
+}
+
+impl Debug for StoreContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("StoreContext").finish()
+    }
+}
+
 pub(crate) struct StoreContextInner {
     raw: *mut sys::signal_protocol_store_context,
     // the global context must outlive `signal_protocol_store_context`
     #[allow(dead_code)]
     ctx: Rc<ContextInner>,
+    has_sender_key_store: Cell<bool>,
 }
 
 impl Drop for StoreContextInner {
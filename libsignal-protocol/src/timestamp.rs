@@ -0,0 +1,101 @@
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+    time::{Duration, SystemTime, SystemTimeError},
+};
+
+/// A point in time, stored as the number of milliseconds since the UNIX epoch.
+///
+/// This is the precision `libsignal-protocol-c` (and the wider Signal
+/// ecosystem) uses for signed pre-key timestamps.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Timestamp(u64);
+
+impl Timestamp {
+    /// The UNIX epoch, `1970-01-01 00:00:00 UTC`.
+    pub const UNIX_EPOCH: Timestamp = Timestamp(0);
+
+    /// The current time, according to the system clock.
+    ///
+    /// A clock set before the UNIX epoch is treated as being at the epoch.
+    pub fn now() -> Timestamp {
+        Timestamp::try_from(SystemTime::now()).unwrap_or(Timestamp::UNIX_EPOCH)
+    }
+
+    pub const fn from_millis(millis: u64) -> Timestamp { Timestamp(millis) }
+
+    pub const fn as_millis(self) -> u64 { self.0 }
+
+    /// Create a timestamp from the time elapsed since the UNIX epoch,
+    /// truncated to milliseconds.
+    pub fn from_unix_duration(elapsed: Duration) -> Timestamp {
+        let millis = elapsed
+            .as_secs()
+            .saturating_mul(1000)
+            .saturating_add(u64::from(elapsed.subsec_millis()));
+
+        Timestamp(millis)
+    }
+
+    /// The time elapsed since the UNIX epoch.
+    pub fn as_unix_duration(self) -> Duration { Duration::from_millis(self.0) }
+
+    pub fn to_system_time(self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + self.as_unix_duration()
+    }
+}
+
+impl TryFrom<SystemTime> for Timestamp {
+    type Error = SystemTimeError;
+
+    /// Convert a [`SystemTime`], failing if it is before the UNIX epoch.
+    fn try_from(time: SystemTime) -> Result<Timestamp, SystemTimeError> {
+        let elapsed = time.duration_since(SystemTime::UNIX_EPOCH)?;
+        Ok(Timestamp::from_unix_duration(elapsed))
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(timestamp: Timestamp) -> SystemTime { timestamp.to_system_time() }
+}
+
+impl From<Timestamp> for u64 {
+    fn from(timestamp: Timestamp) -> u64 { timestamp.0 }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}ms", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_through_system_time() {
+        let timestamp = Timestamp::from_millis(1_411_152_577_123);
+
+        let time = SystemTime::from(timestamp);
+
+        assert_eq!(Timestamp::try_from(time).unwrap(), timestamp);
+    }
+
+    #[test]
+    fn sub_millisecond_precision_is_truncated() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_micros(1_999);
+
+        assert_eq!(
+            Timestamp::try_from(time).unwrap(),
+            Timestamp::from_millis(1)
+        );
+    }
+
+    #[test]
+    fn times_before_the_epoch_are_rejected() {
+        let time = SystemTime::UNIX_EPOCH - Duration::from_secs(1);
+
+        assert!(Timestamp::try_from(time).is_err());
+    }
+}
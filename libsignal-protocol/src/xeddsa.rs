@@ -0,0 +1,151 @@
+//! Conversions between the curve25519 (X25519) keys used by the protocol and
+//! their Ed25519 equivalents.
+//!
+//! `libsignal-protocol-c` signs with XEdDSA, which is Ed25519 over the
+//! birationally equivalent Edwards form of an X25519 key. A Montgomery point
+//! only determines the Edwards `y` coordinate, so the sign bit of `x` is
+//! smuggled through the otherwise unused top bit of the signature's `s`.
+//! Moving that bit between the signature and the public key is all it takes
+//! to turn an XEdDSA signature into one standard Ed25519 tooling can verify,
+//! and vice versa.
+
+use crate::{errors::InternalError, keys::PublicKey};
+use curve25519_dalek::{
+    edwards::CompressedEdwardsY, montgomery::MontgomeryPoint,
+};
+use failure::Error;
+use std::convert::TryFrom;
+
+/// The type byte `libsignal-protocol-c` puts in front of curve25519 keys.
+const DJB_TYPE: u8 = 0x05;
+const KEY_LEN: usize = 32;
+const SIGNATURE_LEN: usize = 64;
+const SIGN_BIT: u8 = 0x80;
+
+/// Convert a curve25519 public key to the Ed25519 public key with the given
+/// sign bit.
+pub fn to_ed25519_public_key(
+    key: &PublicKey,
+    sign_bit: bool,
+) -> Result<[u8; KEY_LEN], Error> {
+    let montgomery = MontgomeryPoint(montgomery_bytes(key)?);
+
+    montgomery
+        .to_edwards(sign_bit as u8)
+        .map(|point| point.compress().to_bytes())
+        .ok_or_else(|| InternalError::InvalidKey.into())
+}
+
+/// Convert an Ed25519 public key to its curve25519 form.
+///
+/// The sign of the Edwards `x` coordinate is lost in the process, use
+/// [`from_ed25519_signature()`] to carry it across as part of a signature.
+pub fn from_ed25519_public_key(
+    ed25519_key: &[u8; KEY_LEN],
+) -> Result<PublicKey, Error> {
+    let point = CompressedEdwardsY(*ed25519_key)
+        .decompress()
+        .ok_or(InternalError::InvalidKey)?;
+
+    let mut serialized = Vec::with_capacity(1 + KEY_LEN);
+    serialized.push(DJB_TYPE);
+    serialized.extend_from_slice(point.to_montgomery().as_bytes());
+
+    PublicKey::try_from(serialized)
+}
+
+/// Turn an XEdDSA signature from `libsignal-protocol-c` into an Ed25519
+/// public key and signature which can be checked by any Ed25519
+/// implementation.
+pub fn to_ed25519_signature(
+    key: &PublicKey,
+    signature: &[u8],
+) -> Result<([u8; KEY_LEN], [u8; SIGNATURE_LEN]), Error> {
+    if signature.len() != SIGNATURE_LEN {
+        return Err(InternalError::InvalidArgument.into());
+    }
+
+    let mut ed25519_signature = [0; SIGNATURE_LEN];
+    ed25519_signature.copy_from_slice(signature);
+
+    let sign_bit = ed25519_signature[SIGNATURE_LEN - 1] & SIGN_BIT != 0;
+    ed25519_signature[SIGNATURE_LEN - 1] &= !SIGN_BIT;
+
+    let ed25519_key = to_ed25519_public_key(key, sign_bit)?;
+
+    Ok((ed25519_key, ed25519_signature))
+}
+
+/// Turn a standard Ed25519 public key and signature into a curve25519 public
+/// key and XEdDSA signature which [`PublicKey::verify_signature()`] accepts.
+pub fn from_ed25519_signature(
+    ed25519_key: &[u8; KEY_LEN],
+    signature: &[u8; SIGNATURE_LEN],
+) -> Result<(PublicKey, [u8; SIGNATURE_LEN]), Error> {
+    // a canonical s is less than the group order, so its top bit is free
+    if signature[SIGNATURE_LEN - 1] & SIGN_BIT != 0 {
+        return Err(InternalError::InvalidArgument.into());
+    }
+
+    let key = from_ed25519_public_key(ed25519_key)?;
+
+    let mut xeddsa_signature = *signature;
+    xeddsa_signature[SIGNATURE_LEN - 1] |= ed25519_key[KEY_LEN - 1] & SIGN_BIT;
+
+    Ok((key, xeddsa_signature))
+}
+
+fn montgomery_bytes(key: &PublicKey) -> Result<[u8; KEY_LEN], Error> {
+    let mut serialized = Vec::new();
+    key.serialize(&mut serialized)?;
+
+    match serialized.split_first() {
+        Some((&DJB_TYPE, rest)) if rest.len() == KEY_LEN => {
+            let mut bytes = [0; KEY_LEN];
+            bytes.copy_from_slice(rest);
+            Ok(bytes)
+        },
+        _ => Err(InternalError::InvalidKey.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Context;
+
+    #[test]
+    fn public_keys_round_trip_through_ed25519() {
+        let ctx = Context::default();
+        let key = ctx.generate_key_pair().unwrap().public().unwrap();
+
+        for &sign_bit in &[false, true] {
+            let ed25519_key = to_ed25519_public_key(&key, sign_bit).unwrap();
+            assert_eq!(ed25519_key[KEY_LEN - 1] & SIGN_BIT != 0, sign_bit);
+
+            let got = from_ed25519_public_key(&ed25519_key).unwrap();
+            assert_eq!(got, key);
+        }
+    }
+
+    #[test]
+    fn signatures_round_trip_through_ed25519() {
+        let ctx = Context::default();
+        let key_pair = ctx.generate_key_pair().unwrap();
+        let key = key_pair.public().unwrap();
+        let message = b"Hello, World!";
+        let signature = ctx
+            .calculate_signature(&key_pair.private().unwrap(), message)
+            .unwrap();
+
+        let (ed25519_key, ed25519_signature) =
+            to_ed25519_signature(&key, signature.as_slice()).unwrap();
+        assert_eq!(ed25519_signature[SIGNATURE_LEN - 1] & SIGN_BIT, 0);
+
+        let (got_key, got_signature) =
+            from_ed25519_signature(&ed25519_key, &ed25519_signature).unwrap();
+        assert_eq!(got_key, key);
+        assert_eq!(&got_signature[..], signature.as_slice());
+        got_key.verify_signature(message, &got_signature).unwrap();
+    }
+}
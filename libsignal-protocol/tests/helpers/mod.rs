@@ -1,5 +1,5 @@
 use libsignal_protocol::{
-    crypto::{Crypto, Sha256Hmac, Sha512Digest},
+    crypto::{Crypto, Sha256Digest, Sha256Hmac, Sha512Digest},
     InternalError, SignalCipherType,
 };
 
@@ -42,6 +42,10 @@ impl<C: Crypto> Crypto for MockCrypto<C> {
         self.inner.hmac_sha256(key)
     }
 
+    fn sha256_digest(&self) -> Result<Box<dyn Sha256Digest>, InternalError> {
+        self.inner.sha256_digest()
+    }
+
     fn sha512_digest(&self) -> Result<Box<dyn Sha512Digest>, InternalError> {
         self.inner.sha512_digest()
     }
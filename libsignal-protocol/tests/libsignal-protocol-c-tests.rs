@@ -3,10 +3,10 @@ mod helpers;
 use crate::helpers::{fake_random_generator, MockCrypto};
 use libsignal_protocol::{
     crypto::DefaultCrypto,
-    keys::{PrivateKey, PublicKey},
-    Context,
+    keys::{IdentityKeyPair, PreKey, PrivateKey, PublicKey},
+    ContactPacket, Context, DeviceId, PreKeyId, SignedPreKeyId, Timestamp,
 };
-use std::time::{Duration, SystemTime};
+use std::convert::TryFrom;
 
 fn mock_ctx() -> Context {
     Context::new(
@@ -40,6 +40,39 @@ fn test_curve25519_generate_public() {
     assert_eq!(got, expected_public_key);
 }
 
+#[test]
+fn keys_can_be_parsed_without_a_context() {
+    let ctx = mock_ctx();
+    let identity = ctx.generate_identity_key_pair().unwrap();
+    let serialized = identity.serialize().unwrap().as_slice().to_vec();
+    let mut public = Vec::new();
+    identity
+        .public_key()
+        .unwrap()
+        .serialize(&mut public)
+        .unwrap();
+
+    let got = IdentityKeyPair::try_from(serialized).unwrap();
+    assert_eq!(got.public_key().unwrap(), identity.public_key().unwrap());
+    assert_eq!(
+        PublicKey::try_from(&public[..]).unwrap(),
+        identity.public_key().unwrap()
+    );
+    assert!(PublicKey::try_from(&public[1..]).is_err());
+
+    let pre_key = ctx
+        .generate_pre_keys(PreKeyId::new(1), 1)
+        .unwrap()
+        .iter()
+        .next()
+        .unwrap();
+    let serialized = pre_key.serialize().unwrap();
+    assert_eq!(
+        PreKey::try_from(serialized.as_slice()).unwrap().id(),
+        PreKeyId::new(1)
+    );
+}
+
 /// See https://github.com/signalapp/libsignal-protocol-c/blob/7bd0e5fee0ebde15c45fffcd631b74d188fd5551/tests/test_key_helper.c#L90
 #[test]
 fn test_generate_pre_keys() {
@@ -78,7 +111,7 @@ fn test_generate_pre_keys() {
 
     let ctx = mock_ctx();
 
-    let pre_keys = ctx.generate_pre_keys(1, 4).unwrap();
+    let pre_keys = ctx.generate_pre_keys(PreKeyId::new(1), 4).unwrap();
     let mut iter = pre_keys.iter();
 
     let pre_key_1 = iter.next().unwrap();
@@ -98,6 +131,60 @@ fn test_generate_pre_keys() {
     assert_eq!(PRE_KEY4, pre_key_4_serialized.as_slice());
 }
 
+#[test]
+fn generate_pre_key_records() {
+    let ctx = mock_ctx();
+
+    let records = ctx.generate_pre_key_records(PreKeyId::new(1), 4).unwrap();
+
+    let ids: Vec<u32> =
+        records.iter().map(|record| record.id().value()).collect();
+    assert_eq!(ids, vec![1, 2, 3, 4]);
+    for record in &records {
+        assert_eq!(
+            record.serialized(),
+            record.pre_key().serialize().unwrap().as_slice()
+        );
+    }
+}
+
+#[test]
+fn contact_packets_round_trip() {
+    let ctx = mock_ctx();
+    let identity_key_pair = ctx.generate_identity_key_pair().unwrap();
+    let signed_pre_key = ctx
+        .generate_signed_pre_key(
+            &identity_key_pair,
+            SignedPreKeyId::new(5),
+            Timestamp::now(),
+        )
+        .unwrap();
+    let pre_keys = ctx.generate_pre_keys(PreKeyId::new(1), 1).unwrap();
+    let pre_key = pre_keys.iter().next().unwrap();
+
+    let packet = ContactPacket::new(
+        1234,
+        DeviceId::new(2).unwrap(),
+        &identity_key_pair,
+        &signed_pre_key,
+        Some(&pre_key),
+    )
+    .unwrap();
+    let bytes = packet.to_bytes().unwrap();
+    let got = ContactPacket::from_bytes(&bytes).unwrap();
+
+    let bundle = got.bundle();
+    assert_eq!(bundle.registration_id(), 1234);
+    assert_eq!(bundle.device_id(), DeviceId::new(2).unwrap());
+    assert_eq!(bundle.pre_key_id(), Some(PreKeyId::new(1)));
+    assert_eq!(bundle.signed_pre_key_id(), SignedPreKeyId::new(5));
+    assert_eq!(
+        bundle.identity_key(),
+        identity_key_pair.public_key().unwrap()
+    );
+    assert_eq!(got.to_bytes().unwrap(), bytes);
+}
+
 #[test]
 fn test_generate_signed_pre_key() {
     const TIMESTAMP: u64 = 1411152577000;
@@ -124,8 +211,8 @@ fn test_generate_signed_pre_key() {
     let signed = ctx
         .generate_signed_pre_key(
             &identity_key_pair,
-            1234,
-            SystemTime::UNIX_EPOCH + Duration::from_secs(TIMESTAMP),
+            SignedPreKeyId::new(1234),
+            Timestamp::from_millis(TIMESTAMP),
         )
         .unwrap();
 
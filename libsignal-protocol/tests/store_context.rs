@@ -0,0 +1,1042 @@
+#[cfg(feature = "crypto-openssl")]
+use libsignal_protocol::{
+    crypto::OpenSSLCrypto, CiphertextType, ContactPacket, GroupCipher,
+    MetadataKey, Padding, PreKeyBundle, PreKeySignalMessage,
+    SenderKeyDistributionMessage, SenderKeyMessage, SessionBuilder,
+    SignalMessage,
+};
+use libsignal_protocol::{
+    Address, Buffer, Context, DeviceId, GroupSessionBuilder, IdentityKeyPair,
+    IdentityKeyStore, InternalError, PreKeyId, PreKeyStore, PrivateKey,
+    PublicKey, SenderKeyName, SenderKeyStore, SessionCipher, SessionRecord,
+    SessionStore, SignedPreKeyId, SignedPreKeyStore, StoreContext, Timestamp,
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{self, Write},
+    rc::Rc,
+};
+
+/// A simple in-memory key store which can be used for both pre-keys and
+/// signed pre-keys.
+#[derive(Debug, Default, Clone)]
+struct InMemoryKeys(Rc<RefCell<HashMap<u32, Vec<u8>>>>);
+
+impl InMemoryKeys {
+    fn load(&self, id: u32, writer: &mut dyn Write) -> io::Result<()> {
+        match self.0.borrow().get(&id) {
+            Some(body) => writer.write_all(body),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn store(&self, id: u32, body: &[u8]) -> Result<(), InternalError> {
+        self.0.borrow_mut().insert(id, body.to_vec());
+        Ok(())
+    }
+
+    fn contains(&self, id: u32) -> bool { self.0.borrow().contains_key(&id) }
+
+    fn remove(&self, id: u32) -> Result<(), InternalError> {
+        self.0.borrow_mut().remove(&id);
+        Ok(())
+    }
+}
+
+impl PreKeyStore for InMemoryKeys {
+    fn load(&self, id: PreKeyId, writer: &mut dyn Write) -> io::Result<()> {
+        InMemoryKeys::load(self, id.value(), writer)
+    }
+
+    fn store(&self, id: PreKeyId, body: &[u8]) -> Result<(), InternalError> {
+        InMemoryKeys::store(self, id.value(), body)
+    }
+
+    fn contains(&self, id: PreKeyId) -> bool {
+        InMemoryKeys::contains(self, id.value())
+    }
+
+    fn remove(&self, id: PreKeyId) -> Result<(), InternalError> {
+        InMemoryKeys::remove(self, id.value())
+    }
+}
+
+impl SignedPreKeyStore for InMemoryKeys {
+    fn load(
+        &self,
+        id: SignedPreKeyId,
+        writer: &mut dyn Write,
+    ) -> io::Result<()> {
+        InMemoryKeys::load(self, id.value(), writer)
+    }
+
+    fn store(
+        &self,
+        id: SignedPreKeyId,
+        body: &[u8],
+    ) -> Result<(), InternalError> {
+        InMemoryKeys::store(self, id.value(), body)
+    }
+
+    fn contains(&self, id: SignedPreKeyId) -> bool {
+        InMemoryKeys::contains(self, id.value())
+    }
+
+    fn remove(&self, id: SignedPreKeyId) -> Result<(), InternalError> {
+        InMemoryKeys::remove(self, id.value())
+    }
+}
+
+/// An in-memory [`SenderKeyStore`].
+#[derive(Debug, Default, Clone)]
+struct InMemorySenderKeys(Rc<RefCell<HashMap<SenderKeyName, Vec<u8>>>>);
+
+impl SenderKeyStore for InMemorySenderKeys {
+    fn load(
+        &self,
+        name: &SenderKeyName,
+    ) -> Result<Option<Buffer>, InternalError> {
+        Ok(self
+            .0
+            .borrow()
+            .get(name)
+            .map(|record| Buffer::from(record.clone())))
+    }
+
+    fn store(
+        &self,
+        name: &SenderKeyName,
+        record: &[u8],
+    ) -> Result<(), InternalError> {
+        self.0.borrow_mut().insert(name.clone(), record.to_vec());
+        Ok(())
+    }
+}
+
+type Sessions = HashMap<(String, DeviceId), (Vec<u8>, Vec<u8>)>;
+
+/// An in-memory [`SessionStore`].
+#[derive(Debug, Default, Clone)]
+struct InMemorySessions(Rc<RefCell<Sessions>>);
+
+impl SessionStore for InMemorySessions {
+    fn load_session(
+        &self,
+        address: &Address,
+    ) -> Result<(Buffer, Buffer), InternalError> {
+        match self.0.borrow().get(&key_for(address)) {
+            Some((record, user_record)) => Ok((
+                Buffer::from(record.clone()),
+                Buffer::from(user_record.clone()),
+            )),
+            None => Ok((Buffer::new(), Buffer::new())),
+        }
+    }
+
+    fn get_sub_device_sessions(
+        &self,
+        name: &str,
+    ) -> Result<Vec<DeviceId>, InternalError> {
+        Ok(self
+            .0
+            .borrow()
+            .keys()
+            .filter(|(n, _)| n == name)
+            .map(|(_, device_id)| *device_id)
+            .collect())
+    }
+
+    fn store_session(
+        &self,
+        address: &Address,
+        record: &[u8],
+        user_record: &[u8],
+    ) -> Result<(), InternalError> {
+        self.0
+            .borrow_mut()
+            .insert(key_for(address), (record.to_vec(), user_record.to_vec()));
+        Ok(())
+    }
+
+    fn contains_session(
+        &self,
+        address: &Address,
+    ) -> Result<bool, InternalError> {
+        Ok(self.0.borrow().contains_key(&key_for(address)))
+    }
+
+    fn delete_session(&self, address: &Address) -> Result<bool, InternalError> {
+        Ok(self.0.borrow_mut().remove(&key_for(address)).is_some())
+    }
+
+    fn delete_all_sessions(&self, name: &str) -> Result<usize, InternalError> {
+        let mut sessions = self.0.borrow_mut();
+        let before = sessions.len();
+        sessions.retain(|(n, _), _| n != name);
+        Ok(before - sessions.len())
+    }
+}
+
+type Identities = HashMap<(String, DeviceId), Vec<u8>>;
+
+/// An in-memory [`IdentityKeyStore`] which trusts an identity the first time
+/// it sees it.
+#[derive(Debug, Clone)]
+struct InMemoryIdentities {
+    public_key: PublicKey,
+    private_key: PrivateKey,
+    registration_id: u32,
+    known: Rc<RefCell<Identities>>,
+}
+
+impl InMemoryIdentities {
+    fn new(ctx: &Context) -> InMemoryIdentities {
+        let identity = ctx.generate_identity_key_pair().unwrap();
+
+        InMemoryIdentities {
+            public_key: identity.public_key().unwrap(),
+            private_key: identity.private_key().unwrap(),
+            registration_id: ctx.generate_registration_id(0).unwrap(),
+            known: Rc::default(),
+        }
+    }
+}
+
+impl IdentityKeyStore for InMemoryIdentities {
+    fn get_identity_key_pair(&self) -> Result<IdentityKeyPair, InternalError> {
+        IdentityKeyPair::new(&self.public_key, &self.private_key)
+            .map_err(|_| InternalError::Unknown)
+    }
+
+    fn get_local_registration_id(&self) -> Result<u32, InternalError> {
+        Ok(self.registration_id)
+    }
+
+    fn save_identity(
+        &self,
+        address: &Address,
+        identity_key: &[u8],
+    ) -> Result<(), InternalError> {
+        let mut known = self.known.borrow_mut();
+
+        if identity_key.is_empty() {
+            known.remove(&key_for(address));
+        } else {
+            known.insert(key_for(address), identity_key.to_vec());
+        }
+
+        Ok(())
+    }
+
+    fn is_trusted_identity(
+        &self,
+        address: &Address,
+        identity_key: &[u8],
+    ) -> Result<bool, InternalError> {
+        match self.known.borrow().get(&key_for(address)) {
+            Some(known) => Ok(known.as_slice() == identity_key),
+            None => Ok(true),
+        }
+    }
+}
+
+fn key_for(address: &Address) -> (String, DeviceId) {
+    (address.as_str().unwrap().to_string(), address.device_id())
+}
+
+/// A store context backed by fresh, empty stores.
+fn store_context(ctx: &Context) -> StoreContext {
+    ctx.new_store_context(
+        InMemoryKeys::default(),
+        InMemoryKeys::default(),
+        InMemorySessions::default(),
+        InMemoryIdentities::new(ctx),
+    )
+    .unwrap()
+}
+
+/// One side of a conversation, with its own set of stores.
+#[cfg(feature = "crypto-openssl")]
+struct User {
+    name: &'static str,
+    pre_keys: InMemoryKeys,
+    signed_pre_keys: InMemoryKeys,
+    sessions: InMemorySessions,
+    identities: InMemoryIdentities,
+    store_ctx: StoreContext,
+}
+
+#[cfg(feature = "crypto-openssl")]
+impl User {
+    const PRE_KEY_ID: u32 = 31337;
+    const SIGNED_PRE_KEY_ID: u32 = 22;
+
+    fn new(ctx: &Context, name: &'static str) -> User {
+        let pre_keys = InMemoryKeys::default();
+        let signed_pre_keys = InMemoryKeys::default();
+        let sessions = InMemorySessions::default();
+        let identities = InMemoryIdentities::new(ctx);
+        let store_ctx = ctx
+            .new_store_context(
+                pre_keys.clone(),
+                signed_pre_keys.clone(),
+                sessions.clone(),
+                identities.clone(),
+            )
+            .unwrap();
+
+        User {
+            name,
+            pre_keys,
+            signed_pre_keys,
+            sessions,
+            identities,
+            store_ctx,
+        }
+    }
+
+    fn address(&self) -> Address<'static> {
+        Address::new(self.name, DeviceId::new(1).unwrap())
+    }
+
+    /// Generate and save a set of pre-keys, returning the bundle a server
+    /// would hand out for this user.
+    fn pre_key_bundle(&self, ctx: &Context) -> PreKeyBundle {
+        let identity = self.identities.get_identity_key_pair().unwrap();
+        let pre_key = ctx
+            .generate_pre_keys(PreKeyId::new(User::PRE_KEY_ID), 1)
+            .unwrap()
+            .iter()
+            .next()
+            .unwrap();
+        PreKeyStore::store(
+            &self.pre_keys,
+            pre_key.id(),
+            pre_key.serialize().unwrap().as_slice(),
+        )
+        .unwrap();
+        let signed_pre_key = ctx
+            .generate_signed_pre_key(
+                &identity,
+                SignedPreKeyId::new(User::SIGNED_PRE_KEY_ID),
+                Timestamp::now(),
+            )
+            .unwrap();
+        SignedPreKeyStore::store(
+            &self.signed_pre_keys,
+            signed_pre_key.id(),
+            signed_pre_key.serialize().unwrap().as_slice(),
+        )
+        .unwrap();
+
+        self.store_ctx
+            .local_pre_key_bundle(
+                self.address().device_id(),
+                pre_key.id(),
+                signed_pre_key.id(),
+            )
+            .unwrap()
+    }
+
+    /// Start a session with `other` using one of their pre-key bundles.
+    fn start_session_with(&self, ctx: &Context, other: &User) {
+        let builder =
+            SessionBuilder::new(ctx, self.store_ctx.clone(), other.address())
+                .unwrap();
+
+        builder
+            .process_pre_key_bundle(&other.pre_key_bundle(ctx))
+            .unwrap();
+    }
+
+    fn cipher_for(&self, ctx: &Context, other: &User) -> SessionCipher {
+        SessionCipher::new(ctx, &self.store_ctx, &other.address()).unwrap()
+    }
+}
+
+/// Send a message over the wire, expecting it to be a
+/// [`PreKeySignalMessage`].
+#[cfg(feature = "crypto-openssl")]
+fn send_pre_key_message(
+    ctx: &Context,
+    cipher: &SessionCipher,
+    message: &[u8],
+) -> PreKeySignalMessage {
+    let encrypted = cipher.encrypt(message).unwrap();
+    assert_eq!(encrypted.get_type(), CiphertextType::PreKey);
+
+    PreKeySignalMessage::deserialize(ctx, encrypted.serialize()).unwrap()
+}
+
+/// Send a message over the wire, expecting it to be a [`SignalMessage`].
+#[cfg(feature = "crypto-openssl")]
+fn send_signal_message(
+    ctx: &Context,
+    cipher: &SessionCipher,
+    message: &[u8],
+) -> SignalMessage {
+    let encrypted = cipher.encrypt(message).unwrap();
+    assert_eq!(encrypted.get_type(), CiphertextType::Signal);
+
+    SignalMessage::deserialize(ctx, encrypted.serialize()).unwrap()
+}
+
+#[test]
+fn load_and_remove_signed_pre_keys() {
+    let ctx = Context::default();
+    let signed_pre_keys = InMemoryKeys::default();
+    let store_ctx = ctx
+        .new_store_context(
+            InMemoryKeys::default(),
+            signed_pre_keys.clone(),
+            InMemorySessions::default(),
+            InMemoryIdentities::new(&ctx),
+        )
+        .unwrap();
+    let identity = ctx.generate_identity_key_pair().unwrap();
+    let signed_pre_key = ctx
+        .generate_signed_pre_key(
+            &identity,
+            SignedPreKeyId::new(5),
+            Timestamp::now(),
+        )
+        .unwrap();
+    let serialized = signed_pre_key.serialize().unwrap();
+    SignedPreKeyStore::store(
+        &signed_pre_keys,
+        SignedPreKeyId::new(5),
+        serialized.as_slice(),
+    )
+    .unwrap();
+
+    assert!(store_ctx
+        .contains_signed_pre_key(SignedPreKeyId::new(5))
+        .unwrap());
+    assert!(!store_ctx
+        .contains_signed_pre_key(SignedPreKeyId::new(6))
+        .unwrap());
+
+    let got = store_ctx
+        .load_signed_pre_key(SignedPreKeyId::new(5))
+        .unwrap();
+    assert_eq!(got.id(), SignedPreKeyId::new(5));
+    assert_eq!(got.get_signature(), signed_pre_key.get_signature());
+    assert!(store_ctx
+        .load_signed_pre_key(SignedPreKeyId::new(6))
+        .is_err());
+
+    store_ctx
+        .remove_signed_pre_key(SignedPreKeyId::new(5))
+        .unwrap();
+    assert!(!store_ctx
+        .contains_signed_pre_key(SignedPreKeyId::new(5))
+        .unwrap());
+}
+
+#[test]
+fn load_and_remove_pre_keys() {
+    let ctx = Context::default();
+    let pre_keys = InMemoryKeys::default();
+    let store_ctx = ctx
+        .new_store_context(
+            pre_keys.clone(),
+            InMemoryKeys::default(),
+            InMemorySessions::default(),
+            InMemoryIdentities::new(&ctx),
+        )
+        .unwrap();
+    for pre_key in ctx.generate_pre_keys(PreKeyId::new(1), 3).unwrap().iter() {
+        let serialized = pre_key.serialize().unwrap();
+        PreKeyStore::store(&pre_keys, pre_key.id(), serialized.as_slice())
+            .unwrap();
+    }
+
+    assert!(store_ctx.contains_pre_key(PreKeyId::new(2)).unwrap());
+    assert!(!store_ctx.contains_pre_key(PreKeyId::new(42)).unwrap());
+
+    let got = store_ctx.load_pre_key(PreKeyId::new(2)).unwrap();
+    assert_eq!(got.id(), PreKeyId::new(2));
+    assert!(store_ctx.load_pre_key(PreKeyId::new(42)).is_err());
+
+    store_ctx.remove_pre_key(PreKeyId::new(2)).unwrap();
+    assert!(!store_ctx.contains_pre_key(PreKeyId::new(2)).unwrap());
+    assert!(store_ctx.contains_pre_key(PreKeyId::new(3)).unwrap());
+}
+
+#[test]
+#[cfg(feature = "crypto-openssl")]
+fn assemble_a_local_pre_key_bundle() {
+    let ctx = Context::new(OpenSSLCrypto).unwrap();
+    let alice = User::new(&ctx, "+14151111111");
+    let identity = alice.identities.get_identity_key_pair().unwrap();
+    let device_id = alice.address().device_id();
+    let pre_key_id = PreKeyId::new(User::PRE_KEY_ID);
+    let signed_pre_key_id = SignedPreKeyId::new(User::SIGNED_PRE_KEY_ID);
+
+    // nothing has been generated yet
+    assert!(alice
+        .store_ctx
+        .local_pre_key_bundle(device_id, pre_key_id, signed_pre_key_id)
+        .is_err());
+
+    alice.pre_key_bundle(&ctx);
+    let bundle = alice
+        .store_ctx
+        .local_pre_key_bundle(device_id, pre_key_id, signed_pre_key_id)
+        .unwrap();
+
+    bundle.verify().unwrap();
+    assert_eq!(bundle.registration_id(), alice.identities.registration_id);
+    assert_eq!(bundle.device_id(), device_id);
+    assert_eq!(bundle.pre_key_id(), Some(pre_key_id));
+    assert_eq!(
+        bundle.pre_key(),
+        Some(
+            alice
+                .store_ctx
+                .load_pre_key(pre_key_id)
+                .unwrap()
+                .key_pair()
+                .public()
+                .unwrap()
+        )
+    );
+    assert_eq!(bundle.signed_pre_key_id(), signed_pre_key_id);
+    assert_eq!(bundle.identity_key(), identity.public_key().unwrap());
+}
+
+#[test]
+fn clones_share_the_same_stores() {
+    let ctx = Context::default();
+    let pre_keys = InMemoryKeys::default();
+    let store_ctx = ctx
+        .new_store_context(
+            pre_keys.clone(),
+            InMemoryKeys::default(),
+            InMemorySessions::default(),
+            InMemoryIdentities::new(&ctx),
+        )
+        .unwrap();
+    let pre_key = ctx
+        .generate_pre_keys(PreKeyId::new(1), 1)
+        .unwrap()
+        .iter()
+        .next()
+        .unwrap();
+    let serialized = pre_key.serialize().unwrap();
+    PreKeyStore::store(&pre_keys, PreKeyId::new(1), serialized.as_slice())
+        .unwrap();
+
+    let other = store_ctx.clone();
+    other.remove_pre_key(PreKeyId::new(1)).unwrap();
+
+    assert!(!store_ctx.contains_pre_key(PreKeyId::new(1)).unwrap());
+}
+
+#[test]
+fn session_ciphers_keep_their_own_copy_of_the_address() {
+    let ctx = Context::default();
+    let store_ctx = store_context(&ctx);
+    let name = String::from("+14159998888");
+    let device_id = DeviceId::new(1).unwrap();
+
+    let cipher =
+        SessionCipher::new(&ctx, &store_ctx, &Address::new(&name, device_id))
+            .unwrap();
+    drop(name);
+
+    let address = cipher.address();
+    assert_eq!(address.as_str().unwrap(), "+14159998888");
+    assert_eq!(address.device_id(), device_id);
+}
+
+#[test]
+#[cfg(feature = "crypto-openssl")]
+fn alice_and_bob_can_talk_to_each_other() {
+    let ctx = Context::new(OpenSSLCrypto).unwrap();
+    let alice = User::new(&ctx, "+14151111111");
+    let bob = User::new(&ctx, "+14152222222");
+    alice.start_session_with(&ctx, &bob);
+    let alice_cipher = alice.cipher_for(&ctx, &bob);
+    let bob_cipher = bob.cipher_for(&ctx, &alice);
+
+    // Alice's first message sets up Bob's side of the session
+    let message = send_pre_key_message(&ctx, &alice_cipher, b"Hello, Bob!");
+    let got = bob_cipher.decrypt_pre_key_signal_message(&message).unwrap();
+    assert_eq!(got.as_slice(), b"Hello, Bob!");
+    assert!(bob.sessions.contains_session(&alice.address()).unwrap());
+    assert!(!bob.pre_keys.contains(User::PRE_KEY_ID));
+
+    // so Bob can reply with a normal message
+    let reply = send_signal_message(&ctx, &bob_cipher, b"Hi, Alice!");
+    let got = alice_cipher.decrypt_signal_message(&reply).unwrap();
+    assert_eq!(got.as_slice(), b"Hi, Alice!");
+
+    // which means Alice doesn't need to send pre-key messages any more
+    let message = send_signal_message(&ctx, &alice_cipher, b"How are you?");
+    let got = bob_cipher.decrypt_signal_message(&message).unwrap();
+    assert_eq!(got.as_slice(), b"How are you?");
+}
+
+#[test]
+#[cfg(feature = "crypto-openssl")]
+fn metadata_keys_are_derived_from_the_session() {
+    let ctx = Context::new(OpenSSLCrypto).unwrap();
+    let alice = User::new(&ctx, "+14151111111");
+    let bob = User::new(&ctx, "+14152222222");
+    alice.start_session_with(&ctx, &bob);
+    let alice_cipher = alice.cipher_for(&ctx, &bob);
+    let bob_cipher = bob.cipher_for(&ctx, &alice);
+
+    let err = MetadataKey::from_session(
+        &ctx,
+        &bob.store_ctx,
+        &alice.address(),
+        "typing",
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<InternalError>(),
+        Some(&InternalError::NoSession)
+    );
+
+    let message = send_pre_key_message(&ctx, &alice_cipher, b"Hello, Bob!");
+    bob_cipher.decrypt_pre_key_signal_message(&message).unwrap();
+
+    let alice_key = MetadataKey::from_session(
+        &ctx,
+        &alice.store_ctx,
+        &bob.address(),
+        "typing",
+    )
+    .unwrap();
+    let bob_key = MetadataKey::from_session(
+        &ctx,
+        &bob.store_ctx,
+        &alice.address(),
+        "typing",
+    )
+    .unwrap();
+    let mac = alice_key.mac(&ctx, b"started").unwrap();
+    bob_key.verify(&ctx, b"started", &mac).unwrap();
+
+    // the key stays the same as the ratchet moves forward
+    let reply = send_signal_message(&ctx, &bob_cipher, b"Hi, Alice!");
+    alice_cipher.decrypt_signal_message(&reply).unwrap();
+    let bob_key = MetadataKey::from_session(
+        &ctx,
+        &bob.store_ctx,
+        &alice.address(),
+        "typing",
+    )
+    .unwrap();
+    bob_key.verify(&ctx, b"started", &mac).unwrap();
+}
+
+#[test]
+fn session_cipher_clones_outlive_the_original() {
+    let ctx = Context::default();
+    let store_ctx = store_context(&ctx);
+    let address = Address::new("+14159998888", DeviceId::new(1).unwrap());
+    let cipher = SessionCipher::new(&ctx, &store_ctx, &address).unwrap();
+
+    let other = cipher.clone();
+    drop(cipher);
+
+    assert_eq!(other.address().as_str().unwrap(), "+14159998888");
+    assert_eq!(other.remote_registration_id().unwrap(), 0);
+}
+
+#[test]
+fn there_is_no_remote_registration_id_without_a_session() {
+    let ctx = Context::default();
+    let store_ctx = store_context(&ctx);
+    let address = Address::new("+14159998888", DeviceId::new(1).unwrap());
+    let cipher = SessionCipher::new(&ctx, &store_ctx, &address).unwrap();
+
+    // a missing session is loaded as a fresh record, which doesn't know the
+    // remote registration ID yet
+    let got = cipher.remote_registration_id().unwrap();
+
+    assert_eq!(got, 0);
+}
+
+#[test]
+fn sender_keys_are_saved_in_the_sender_key_store() {
+    let ctx = Context::default();
+    let store_ctx = store_context(&ctx);
+    let sender_keys = InMemorySenderKeys::default();
+    store_ctx.set_sender_key_store(sender_keys.clone()).unwrap();
+    let builder = GroupSessionBuilder::new(&ctx, &store_ctx).unwrap();
+    let sender = Address::new("+14159998888", DeviceId::new(1).unwrap());
+    let name = SenderKeyName::new(b"group", &sender);
+
+    let first = builder.create_session(&name).unwrap();
+    assert!(sender_keys.0.borrow().contains_key(&name));
+
+    // the second time around the sender key is loaded from the store
+    let second = builder.create_session(&name).unwrap();
+    assert_eq!(first.id(), second.id());
+    assert_eq!(sender_keys.0.borrow().len(), 1);
+}
+
+#[test]
+fn only_one_sender_key_store_can_be_registered() {
+    let ctx = Context::default();
+    let store_ctx = store_context(&ctx);
+
+    store_ctx
+        .set_sender_key_store(InMemorySenderKeys::default())
+        .unwrap();
+
+    assert!(store_ctx
+        .set_sender_key_store(InMemorySenderKeys::default())
+        .is_err());
+}
+
+#[test]
+fn load_a_session_record() {
+    let ctx = Context::default();
+    let record = SessionRecord::new(&ctx).unwrap();
+    let serialized = record.serialize().unwrap();
+    let sessions = InMemorySessions::default();
+    let store_ctx = ctx
+        .new_store_context(
+            InMemoryKeys::default(),
+            InMemoryKeys::default(),
+            sessions.clone(),
+            InMemoryIdentities::new(&ctx),
+        )
+        .unwrap();
+    let address = Address::new("+14159998888", DeviceId::new(1).unwrap());
+    sessions
+        .store_session(&address, serialized.as_slice(), &[])
+        .unwrap();
+
+    let got = store_ctx.load_session(&address).unwrap();
+
+    assert_eq!(got.serialize().unwrap(), serialized);
+}
+
+#[test]
+fn missing_sessions_are_loaded_as_fresh_records() {
+    let ctx = Context::default();
+    let store_ctx = store_context(&ctx);
+    let address = Address::new("+14159998888", DeviceId::new(1).unwrap());
+
+    let got = store_ctx.load_session(&address).unwrap();
+
+    assert!(got.is_fresh());
+}
+
+#[test]
+#[cfg(feature = "crypto-openssl")]
+fn the_remote_registration_id_comes_from_the_session() {
+    let ctx = Context::new(OpenSSLCrypto).unwrap();
+    let alice = User::new(&ctx, "+14151111111");
+    let bob = User::new(&ctx, "+14152222222");
+    alice.start_session_with(&ctx, &bob);
+    let alice_cipher = alice.cipher_for(&ctx, &bob);
+    let bob_cipher = bob.cipher_for(&ctx, &alice);
+
+    // Alice learns Bob's registration ID from his pre-key bundle
+    assert_eq!(
+        alice_cipher.remote_registration_id().unwrap(),
+        bob.identities.registration_id
+    );
+
+    // and Bob learns Alice's from her first message
+    let message = send_pre_key_message(&ctx, &alice_cipher, b"Hello, Bob!");
+    bob_cipher.decrypt_pre_key_signal_message(&message).unwrap();
+    assert_eq!(
+        bob_cipher.remote_registration_id().unwrap(),
+        alice.identities.registration_id
+    );
+}
+
+#[test]
+#[cfg(feature = "crypto-openssl")]
+fn bundles_with_an_untrusted_identity_are_rejected() {
+    let ctx = Context::new(OpenSSLCrypto).unwrap();
+    let alice = User::new(&ctx, "+14151111111");
+    let bob = User::new(&ctx, "+14152222222");
+    // Alice has already seen a different identity key for Bob
+    let mut someone_else = Vec::new();
+    ctx.generate_identity_key_pair()
+        .unwrap()
+        .public_key()
+        .unwrap()
+        .serialize(&mut someone_else)
+        .unwrap();
+    alice
+        .identities
+        .save_identity(&bob.address(), &someone_else)
+        .unwrap();
+    let builder =
+        SessionBuilder::new(&ctx, alice.store_ctx.clone(), bob.address())
+            .unwrap();
+
+    let err = builder
+        .process_pre_key_bundle(&bob.pre_key_bundle(&ctx))
+        .unwrap_err();
+
+    assert_eq!(
+        err.downcast_ref::<InternalError>(),
+        Some(&InternalError::UntrustedIdentity)
+    );
+    assert!(!alice.sessions.contains_session(&bob.address()).unwrap());
+}
+
+#[test]
+#[cfg(feature = "crypto-openssl")]
+fn identities_can_be_vetoed_before_anything_is_saved() {
+    let ctx = Context::new(OpenSSLCrypto).unwrap();
+    let alice = User::new(&ctx, "+14151111111");
+    let bob = User::new(&ctx, "+14152222222");
+    let bundle = bob.pre_key_bundle(&ctx);
+    let builder =
+        SessionBuilder::new(&ctx, alice.store_ctx.clone(), bob.address())
+            .unwrap();
+
+    let err = builder
+        .process_pre_key_bundle_with_callback(&bundle, |identity_key| {
+            assert_eq!(*identity_key, bob.identities.public_key);
+            Err(failure::err_msg("Not the pinned key"))
+        })
+        .unwrap_err();
+
+    assert_eq!(err.to_string(), "Not the pinned key");
+    assert!(!alice.sessions.contains_session(&bob.address()).unwrap());
+    assert!(alice.identities.known.borrow().is_empty());
+
+    builder
+        .process_pre_key_bundle_with_callback(&bundle, |_| Ok(()))
+        .unwrap();
+    assert!(alice.sessions.contains_session(&bob.address()).unwrap());
+}
+
+#[test]
+#[cfg(feature = "crypto-openssl")]
+fn start_a_session_from_a_contact_packet() {
+    let ctx = Context::new(OpenSSLCrypto).unwrap();
+    let alice = User::new(&ctx, "+14151111111");
+    let bob = User::new(&ctx, "+14152222222");
+    let bytes = ContactPacket::from(bob.pre_key_bundle(&ctx))
+        .to_bytes()
+        .unwrap();
+    let packet = ContactPacket::from_bytes(&bytes).unwrap();
+    let builder =
+        SessionBuilder::new(&ctx, alice.store_ctx.clone(), bob.address())
+            .unwrap();
+
+    builder.process_contact_packet(&packet).unwrap();
+
+    let alice_cipher = alice.cipher_for(&ctx, &bob);
+    let bob_cipher = bob.cipher_for(&ctx, &alice);
+    let message = send_pre_key_message(&ctx, &alice_cipher, b"Hello, Bob!");
+    let got = bob_cipher.decrypt_pre_key_signal_message(&message).unwrap();
+    assert_eq!(got.as_slice(), b"Hello, Bob!");
+}
+
+#[test]
+#[cfg(feature = "crypto-openssl")]
+fn failing_decryption_callbacks_leave_the_session_alone() {
+    let ctx = Context::new(OpenSSLCrypto).unwrap();
+    let alice = User::new(&ctx, "+14151111111");
+    let bob = User::new(&ctx, "+14152222222");
+    alice.start_session_with(&ctx, &bob);
+    let alice_cipher = alice.cipher_for(&ctx, &bob);
+    let bob_cipher = bob.cipher_for(&ctx, &alice);
+
+    // a failed pre-key message doesn't create a session or use up the
+    // one-time pre-key
+    let message = send_pre_key_message(&ctx, &alice_cipher, b"Hello, Bob!");
+    let err = bob_cipher
+        .decrypt_pre_key_signal_message_with_callback(&message, |_| {
+            Err(failure::err_msg("Disk full"))
+        })
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Disk full");
+    assert!(!bob.sessions.contains_session(&alice.address()).unwrap());
+    assert!(bob.pre_keys.contains(User::PRE_KEY_ID));
+
+    // so the message can be decrypted again later
+    let got = bob_cipher.decrypt_pre_key_signal_message(&message).unwrap();
+    assert_eq!(got.as_slice(), b"Hello, Bob!");
+
+    // and a failed signal message doesn't update the stored session
+    let reply = send_signal_message(&ctx, &bob_cipher, b"Hi, Alice!");
+    alice_cipher.decrypt_signal_message(&reply).unwrap();
+    let message = send_signal_message(&ctx, &alice_cipher, b"How are you?");
+    let (before, _) = bob.sessions.load_session(&alice.address()).unwrap();
+    let err = bob_cipher
+        .decrypt_signal_message_with_callback(&message, |_| {
+            Err(failure::err_msg("Disk full"))
+        })
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Disk full");
+    let (after, _) = bob.sessions.load_session(&alice.address()).unwrap();
+    assert_eq!(before.as_slice(), after.as_slice());
+
+    let got = bob_cipher.decrypt_signal_message(&message).unwrap();
+    assert_eq!(got.as_slice(), b"How are you?");
+}
+
+#[test]
+#[cfg(feature = "crypto-openssl")]
+fn ciphers_reject_messages_outside_their_pinned_versions() {
+    let ctx = Context::new(OpenSSLCrypto).unwrap();
+    let alice = User::new(&ctx, "+14151111111");
+    let bob = User::new(&ctx, "+14152222222");
+    alice.start_session_with(&ctx, &bob);
+    let alice_cipher = alice.cipher_for(&ctx, &bob);
+    let bob_cipher = bob.cipher_for(&ctx, &alice);
+    assert_eq!(bob_cipher.versions(), 3..=3);
+
+    // every message is created with version 3, which is too old here
+    let message = send_pre_key_message(&ctx, &alice_cipher, b"Hello, Bob!");
+    let err = bob
+        .cipher_for(&ctx, &alice)
+        .with_versions(4..=4)
+        .decrypt_pre_key_signal_message(&message)
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<InternalError>(),
+        Some(&InternalError::LegacyMessage)
+    );
+    assert!(!bob.sessions.contains_session(&alice.address()).unwrap());
+    bob_cipher.decrypt_pre_key_signal_message(&message).unwrap();
+
+    // ... and too new here
+    let reply = send_signal_message(&ctx, &bob_cipher, b"Hi, Alice!");
+    let err = alice
+        .cipher_for(&ctx, &bob)
+        .with_versions(0..=2)
+        .decrypt_signal_message(&reply)
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<InternalError>(),
+        Some(&InternalError::InvalidVersion)
+    );
+    let got = alice_cipher.decrypt_signal_message(&reply).unwrap();
+    assert_eq!(got.as_slice(), b"Hi, Alice!");
+}
+
+#[test]
+#[cfg(feature = "crypto-openssl")]
+fn padding_is_stripped_after_decryption() {
+    let ctx = Context::new(OpenSSLCrypto).unwrap();
+    let alice = User::new(&ctx, "+14151111111");
+    let bob = User::new(&ctx, "+14152222222");
+    alice.start_session_with(&ctx, &bob);
+    let alice_cipher = alice
+        .cipher_for(&ctx, &bob)
+        .with_padding(Padding::default());
+    let bob_cipher = bob
+        .cipher_for(&ctx, &alice)
+        .with_padding(Padding::default());
+
+    let message = send_pre_key_message(&ctx, &alice_cipher, b"Hello, Bob!");
+    let mut seen = Vec::new();
+    let got = bob_cipher
+        .decrypt_pre_key_signal_message_with_callback(&message, |plaintext| {
+            seen.extend_from_slice(plaintext);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(got.as_slice(), b"Hello, Bob!");
+    assert_eq!(seen, b"Hello, Bob!");
+
+    let reply = send_signal_message(&ctx, &bob_cipher, b"Hi, Alice!");
+    let got = alice_cipher.decrypt_signal_message(&reply).unwrap();
+    assert_eq!(got.as_slice(), b"Hi, Alice!");
+
+    // without padding the recipient sees the padded plaintext
+    let message = send_signal_message(&ctx, &alice_cipher, b"How are you?");
+    let got = bob
+        .cipher_for(&ctx, &alice)
+        .decrypt_signal_message(&message)
+        .unwrap();
+    assert_eq!(got.len(), Padding::default().block_size());
+    assert!(got.as_slice().starts_with(b"How are you?"));
+}
+
+/// Set up a group where Bob has received Alice's sender key, returning the
+/// sender key name for Alice and Bob's sender key store.
+#[cfg(feature = "crypto-openssl")]
+fn group_with_alice_and_bob(
+    ctx: &Context,
+    alice: &User,
+    bob: &User,
+) -> (SenderKeyName, InMemorySenderKeys) {
+    alice
+        .store_ctx
+        .set_sender_key_store(InMemorySenderKeys::default())
+        .unwrap();
+    let bob_sender_keys = InMemorySenderKeys::default();
+    bob.store_ctx
+        .set_sender_key_store(bob_sender_keys.clone())
+        .unwrap();
+    let name = SenderKeyName::new(b"group", &alice.address());
+
+    let distribution = GroupSessionBuilder::new(ctx, &alice.store_ctx)
+        .unwrap()
+        .create_session(&name)
+        .unwrap();
+    let distribution = SenderKeyDistributionMessage::deserialize(
+        ctx,
+        distribution.serialize(),
+    )
+    .unwrap();
+    GroupSessionBuilder::new(ctx, &bob.store_ctx)
+        .unwrap()
+        .process_session(&name, &distribution)
+        .unwrap();
+
+    (name, bob_sender_keys)
+}
+
+#[test]
+#[cfg(feature = "crypto-openssl")]
+fn encrypt_and_decrypt_group_messages() {
+    let ctx = Context::new(OpenSSLCrypto).unwrap();
+    let alice = User::new(&ctx, "+14151111111");
+    let bob = User::new(&ctx, "+14152222222");
+    let (name, _) = group_with_alice_and_bob(&ctx, &alice, &bob);
+    let alice_cipher = GroupCipher::new(&ctx, &alice.store_ctx, &name).unwrap();
+    let bob_cipher = GroupCipher::new(&ctx, &bob.store_ctx, &name).unwrap();
+
+    for text in &[&b"Hello, group!"[..], b"Anyone there?"] {
+        let encrypted = alice_cipher.encrypt(text).unwrap();
+        let message =
+            SenderKeyMessage::deserialize(&ctx, encrypted.serialize()).unwrap();
+
+        let got = bob_cipher.clone().decrypt(&message).unwrap();
+
+        assert_eq!(got.as_slice(), *text);
+    }
+}
+
+#[test]
+#[cfg(feature = "crypto-openssl")]
+fn group_decryption_callback_errors_are_propagated() {
+    let ctx = Context::new(OpenSSLCrypto).unwrap();
+    let alice = User::new(&ctx, "+14151111111");
+    let bob = User::new(&ctx, "+14152222222");
+    let (name, bob_sender_keys) = group_with_alice_and_bob(&ctx, &alice, &bob);
+    let alice_cipher = GroupCipher::new(&ctx, &alice.store_ctx, &name).unwrap();
+    let bob_cipher = GroupCipher::new(&ctx, &bob.store_ctx, &name).unwrap();
+    let message = alice_cipher.encrypt(b"Hello, group!").unwrap();
+    let before = bob_sender_keys.0.borrow().get(&name).cloned();
+
+    let err = bob_cipher
+        .decrypt_with_callback(&message, |_| Err(failure::err_msg("Disk full")))
+        .unwrap_err();
+
+    assert_eq!(err.to_string(), "Disk full");
+    // the sender key wasn't ratcheted forward, so we can try again later
+    assert_eq!(bob_sender_keys.0.borrow().get(&name).cloned(), before);
+    let got = bob_cipher.decrypt(&message).unwrap();
+    assert_eq!(got.as_slice(), b"Hello, group!");
+}